@@ -0,0 +1,716 @@
+//! The binary's command-line arguments
+//!
+//! With no arguments the binary shows the numbered menus in `cli::run_cli`.
+//! Otherwise the first argument names a subcommand and the rest are its
+//! options, so games can be started from scripts:
+//!
+//! ```text
+//! connect4 play --game otto --size large --vs hard --as T
+//! connect4 analyze 7/7/7/7/7/3X3 O
+//! connect4 selfplay --rules popout --levels hard,easy --save game.txt
+//! connect4 replay game.txt
+//! connect4 engine --game otto --level hard
+//! connect4 tournament --games 40 --first hard,center=4 --second hard
+//! connect4 ratings add game.txt alice bob
+//! ```
+
+use std::fmt;
+use crate::board::Difficulty;
+use crate::connect4::Variant;
+use crate::mcts::Rollout;
+use crate::ratings::RatingSystem;
+
+pub const USAGE: &str = "Usage:
+  connect4                      choose what to do from a menu
+  connect4 play [options]       play a game in the terminal
+  connect4 analyze <position>   print the best move in a position, given as
+                                columns played or in notation
+  connect4 selfplay [options]   watch the computer play itself
+  connect4 replay <file>        show a saved game move by move
+  connect4 engine [options]     answer engine protocol commands on stdin
+  connect4 tournament [options] play two engine setups against each other
+  connect4 ratings [options]    show the leaderboards, one for each game,
+                                board size and set of rules
+  connect4 ratings add <file> <name> <name>
+                                rate a saved game, naming its players in
+                                turn order
+  connect4 help                 print this
+
+Levels are easy, hard, perfect and mcts, which plays by Monte Carlo tree
+search rather than minimax.
+
+Board options (play, selfplay, engine and tournament):
+  --game connect4|otto          the game, connect4 if left out
+  --size standard|large|WxH     the board's size, e.g. 8x7
+  --rules classic|popout|five-in-a-row|pop10
+                                Connect 4's rules, classic if left out
+  --win N                       discs in a row that win in Connect 4
+  --players N                   players taking turns in Connect 4
+  --misere                      completing your line or word loses
+  --no-gravity                  pieces go on any empty cell
+
+play:
+  --vs human|LEVEL              who to play against, hard if left out
+  --as SYMBOL                   the side to play, X, O, Y or Z in Connect 4
+                                and T or O in TOOT and OTTO
+  --think MS                    the computer thinks this long per move
+  --save FILE                   write the game's record to FILE
+  --names NAME[,NAME...]        rate the game, the people being called these
+                                in turn order
+  --ledger FILE                 where ratings are kept, ratings.json if
+                                left out
+
+analyze:
+  --game connect4|otto          the game the position is from
+  --level LEVEL                 how hard to search, standard Connect 4
+                                positions with 8 or more discs down are
+                                solved if left out
+  --think MS                    search for this long
+
+selfplay:
+  --levels LEVEL[,LEVEL...]     each player's level, or one for them all
+  --think MS                    each move is thought about this long
+  --save FILE                   write the game's record to FILE
+
+engine:
+  --level LEVEL                 how deep go searches without limits, hard
+                                if left out
+
+tournament:
+  --first SETUP, --second SETUP the two engines, each a level and any of
+                                depth=N, iterations=N, rollout=random or
+                                rollout=heuristic and the heuristic's
+                                weights, like hard,depth=6,center=4 or
+                                mcts,iterations=5000; hard if left out
+  --games N                     games to play, 20 if left out
+  --openings PLIES              length of the balanced openings played
+                                from, 2 if left out
+  --think MS                    each move is thought about this long
+
+  Connect 4's weights are center, line, three, two, block and kept, and
+  TOOT and OTTO's are center, word, three, two and block.
+
+ratings:
+  --system elo|glicko2          the rating to sort by, elo if left out
+  --ledger FILE                 where ratings are kept, ratings.json if
+                                left out";
+
+/// Where ratings are kept unless --ledger says otherwise
+pub const DEFAULT_LEDGER: &str = "ratings.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameKind {
+    Connect4,
+    Otto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Size {
+    Standard,
+    Large,
+    /// width and height
+    Custom(u32, u32),
+}
+
+/// Which board to play on and its rules. The options left as `None` are
+/// Connect 4's, and the board's own defaults are used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardOptions {
+    pub game: GameKind,
+    pub size: Size,
+    pub variant: Variant,
+    pub win_length: Option<u32>,
+    pub players: Option<u32>,
+    pub misere: bool,
+    pub gravity: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayOptions {
+    pub board: BoardOptions,
+    /// the computer's level, or `None` for people on every side
+    pub vs: Option<Difficulty>,
+    /// the symbol of the side the person plays, the first side if left out
+    pub side: Option<char>,
+    pub think_millis: Option<u32>,
+    pub save: Option<String>,
+    /// who the people are, for rating the game, in turn order
+    pub names: Vec<String>,
+    pub ledger: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnalyzeOptions {
+    pub game: GameKind,
+    /// in notation or as the moves played, as `from_notation` reads it
+    pub position: String,
+    pub level: Option<Difficulty>,
+    pub think_millis: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfplayOptions {
+    pub board: BoardOptions,
+    /// one per player in turn order, or one for them all
+    pub levels: Vec<Difficulty>,
+    pub think_millis: Option<u32>,
+    pub save: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineOptions {
+    pub board: BoardOptions,
+    pub level: Difficulty,
+}
+
+/// One side of a tournament
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetupSpec {
+    pub level: Difficulty,
+    pub depth: Option<u32>,
+    /// iterations a move for Monte Carlo tree search
+    pub iterations: Option<u32>,
+    pub rollout: Option<Rollout>,
+    /// the weights to change from the heuristic's usual ones, by name
+    pub weights: Vec<(String, i32)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TournamentOptions {
+    pub board: BoardOptions,
+    pub first: SetupSpec,
+    pub second: SetupSpec,
+    pub games: u32,
+    /// plies in each opening
+    pub openings: u32,
+    pub think_millis: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RatingsOptions {
+    pub ledger: String,
+    pub system: RatingSystem,
+    /// a saved game to rate and its players' names, instead of showing the
+    /// leaderboard
+    pub add: Option<(String, Vec<String>)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Subcommand {
+    Play(PlayOptions),
+    Analyze(AnalyzeOptions),
+    Selfplay(SelfplayOptions),
+    /// the saved game's file name
+    Replay(String),
+    Engine(EngineOptions),
+    Tournament(TournamentOptions),
+    Ratings(RatingsOptions),
+    Help,
+}
+
+/// Why the arguments couldn't be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgError {
+    UnknownSubcommand(String),
+    /// an option the subcommand doesn't take
+    UnknownOption(String),
+    MissingValue(String),
+    /// the option and the value it was given
+    BadValue(String, String),
+    /// a Connect 4 option given for TOOT and OTTO
+    NotForOtto(String),
+    MissingArgument(&'static str),
+    /// an argument left over after the subcommand's
+    Unexpected(String),
+}
+
+impl Default for BoardOptions {
+    fn default() -> BoardOptions {
+        BoardOptions {
+            game: GameKind::Connect4,
+            size: Size::Standard,
+            variant: Variant::Classic,
+            win_length: None,
+            players: None,
+            misere: false,
+            gravity: true,
+        }
+    }
+}
+
+impl Default for SetupSpec {
+    fn default() -> SetupSpec {
+        SetupSpec { level: Difficulty::Hard, depth: None, iterations: None, rollout: None, weights: Vec::new() }
+    }
+}
+
+/// Reads the arguments after the program's name
+pub fn parse(args: &[String]) -> Result<Subcommand, ArgError> {
+    let (name, rest) = match args.split_first() {
+        Some((name, rest)) => (name.as_str(), rest),
+        None => return Ok(Subcommand::Help),
+    };
+    match name {
+        "play" => parse_play(rest).map(Subcommand::Play),
+        "analyze" => parse_analyze(rest).map(Subcommand::Analyze),
+        "selfplay" => parse_selfplay(rest).map(Subcommand::Selfplay),
+        "engine" => parse_engine(rest).map(Subcommand::Engine),
+        "tournament" => parse_tournament(rest).map(Subcommand::Tournament),
+        "ratings" => parse_ratings(rest).map(Subcommand::Ratings),
+        "replay" => {
+            let mut args = parse_options(rest)?.into_iter();
+            match (args.next(), args.next()) {
+                (Some(Arg::Positional(file)), None) => Ok(Subcommand::Replay(file)),
+                (None, _) => Err(ArgError::MissingArgument("the saved game's file name")),
+                (Some(Arg::Positional(_)), Some(extra)) | (Some(extra), _) => Err(extra.unexpected()),
+            }
+        }
+        "help" | "--help" | "-h" => Ok(Subcommand::Help),
+        _ => Err(ArgError::UnknownSubcommand(name.to_string())),
+    }
+}
+
+// an option, with its value if it takes one, or an argument on its own
+enum Arg {
+    Option(String, Option<String>),
+    Positional(String),
+}
+
+impl Arg {
+    fn unexpected(&self) -> ArgError {
+        match self {
+            Arg::Option(name, _) => ArgError::UnknownOption(name.clone()),
+            Arg::Positional(arg) => ArgError::Unexpected(arg.clone()),
+        }
+    }
+}
+
+// options that are flags, every other option takes the argument after it
+const FLAGS: [&str; 2] = ["--misere", "--no-gravity"];
+
+fn parse_options(args: &[String]) -> Result<Vec<Arg>, ArgError> {
+    let mut parsed = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            parsed.push(Arg::Positional(arg.clone()));
+        } else if FLAGS.contains(&arg.as_str()) {
+            parsed.push(Arg::Option(arg.clone(), None));
+        } else {
+            let value = args.next().ok_or_else(|| ArgError::MissingValue(arg.clone()))?;
+            parsed.push(Arg::Option(arg.clone(), Some(value.clone())));
+        }
+    }
+    Ok(parsed)
+}
+
+// sets the board option `name` in `board`, false if it isn't one
+fn board_option(board: &mut BoardOptions, name: &str, value: Option<&str>) -> Result<bool, ArgError> {
+    let bad = || ArgError::BadValue(name.to_string(), value.unwrap_or("").to_string());
+    match (name, value) {
+        ("--game", Some(value)) => board.game = parse_game(value).ok_or_else(bad)?,
+        ("--size", Some(value)) => {
+            board.size = match value {
+                "standard" => Size::Standard,
+                "large" => Size::Large,
+                _ => {
+                    let (width, height) = value.split_once('x').ok_or_else(bad)?;
+                    Size::Custom(width.parse().map_err(|_| bad())?, height.parse().map_err(|_| bad())?)
+                }
+            }
+        }
+        ("--rules", Some(value)) => {
+            board.variant = match value {
+                "classic" => Variant::Classic,
+                "popout" => Variant::PopOut,
+                "five-in-a-row" => Variant::FiveInARow,
+                "pop10" => Variant::Pop10,
+                _ => return Err(bad()),
+            }
+        }
+        ("--win", Some(value)) => board.win_length = Some(value.parse().map_err(|_| bad())?),
+        ("--players", Some(value)) => board.players = Some(value.parse().map_err(|_| bad())?),
+        ("--misere", None) => board.misere = true,
+        ("--no-gravity", None) => board.gravity = false,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+// TOOT and OTTO has one set of rules, on boards of any size
+fn check_otto(board: &BoardOptions) -> Result<(), ArgError> {
+    if board.game != GameKind::Otto {
+        return Ok(());
+    }
+    if board.variant != Variant::Classic {
+        Err(ArgError::NotForOtto("--rules".to_string()))
+    } else if board.win_length.is_some() {
+        Err(ArgError::NotForOtto("--win".to_string()))
+    } else if board.players.is_some() {
+        Err(ArgError::NotForOtto("--players".to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_game(value: &str) -> Option<GameKind> {
+    match value {
+        "connect4" => Some(GameKind::Connect4),
+        "otto" => Some(GameKind::Otto),
+        _ => None,
+    }
+}
+
+fn parse_level(value: &str) -> Option<Difficulty> {
+    match value {
+        "easy" => Some(Difficulty::Easy),
+        "hard" => Some(Difficulty::Hard),
+        "perfect" => Some(Difficulty::Perfect),
+        "mcts" => Some(Difficulty::Mcts),
+        _ => None,
+    }
+}
+
+fn parse_millis(name: &str, value: &str) -> Result<u32, ArgError> {
+    match value.parse() {
+        Ok(millis) if millis > 0 => Ok(millis),
+        _ => Err(ArgError::BadValue(name.to_string(), value.to_string())),
+    }
+}
+
+fn parse_play(args: &[String]) -> Result<PlayOptions, ArgError> {
+    let mut options = PlayOptions {
+        board: BoardOptions::default(),
+        vs: Some(Difficulty::Hard),
+        side: None,
+        think_millis: None,
+        save: None,
+        names: Vec::new(),
+        ledger: DEFAULT_LEDGER.to_string(),
+    };
+    for arg in parse_options(args)? {
+        let (name, value) = match &arg {
+            Arg::Option(name, value) => (name.as_str(), value.as_deref()),
+            Arg::Positional(_) => return Err(arg.unexpected()),
+        };
+        if board_option(&mut options.board, name, value)? {
+            continue;
+        }
+        let bad = || ArgError::BadValue(name.to_string(), value.unwrap_or("").to_string());
+        match (name, value) {
+            ("--names", Some(value)) => options.names = value.split(',').map(String::from).collect(),
+            ("--ledger", Some(value)) => options.ledger = value.to_string(),
+            ("--vs", Some("human")) => options.vs = None,
+            ("--vs", Some(value)) => options.vs = Some(parse_level(value).ok_or_else(bad)?),
+            ("--as", Some(value)) => {
+                let mut symbol = value.chars();
+                match (symbol.next(), symbol.next()) {
+                    (Some(symbol), None) => options.side = Some(symbol.to_ascii_uppercase()),
+                    _ => return Err(bad()),
+                }
+            }
+            ("--think", Some(value)) => options.think_millis = Some(parse_millis(name, value)?),
+            ("--save", Some(value)) => options.save = Some(value.to_string()),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    check_otto(&options.board)?;
+    Ok(options)
+}
+
+fn parse_analyze(args: &[String]) -> Result<AnalyzeOptions, ArgError> {
+    let mut options = AnalyzeOptions {
+        game: GameKind::Connect4,
+        position: String::new(),
+        level: None,
+        think_millis: None,
+    };
+    // a position in notation has a space before the side to move, so it may
+    // come in as two arguments
+    let mut position = Vec::new();
+    for arg in parse_options(args)? {
+        let (name, value) = match arg {
+            Arg::Option(name, Some(value)) => (name, value),
+            Arg::Option(..) => return Err(arg.unexpected()),
+            Arg::Positional(part) => {
+                position.push(part);
+                continue;
+            }
+        };
+        let bad = || ArgError::BadValue(name.clone(), value.clone());
+        match name.as_str() {
+            "--game" => options.game = parse_game(&value).ok_or_else(bad)?,
+            "--level" => options.level = Some(parse_level(&value).ok_or_else(bad)?),
+            "--think" => options.think_millis = Some(parse_millis(&name, &value)?),
+            _ => return Err(ArgError::UnknownOption(name)),
+        }
+    }
+    if position.is_empty() {
+        return Err(ArgError::MissingArgument("a position"));
+    }
+    options.position = position.join(" ");
+    Ok(options)
+}
+
+fn parse_selfplay(args: &[String]) -> Result<SelfplayOptions, ArgError> {
+    let mut options = SelfplayOptions {
+        board: BoardOptions::default(),
+        levels: vec![Difficulty::Hard],
+        think_millis: None,
+        save: None,
+    };
+    for arg in parse_options(args)? {
+        let (name, value) = match &arg {
+            Arg::Option(name, value) => (name.as_str(), value.as_deref()),
+            Arg::Positional(_) => return Err(arg.unexpected()),
+        };
+        if board_option(&mut options.board, name, value)? {
+            continue;
+        }
+        match (name, value) {
+            ("--levels", Some(value)) => {
+                options.levels = value.split(',')
+                    .map(|level| parse_level(level).ok_or_else(|| ArgError::BadValue(name.to_string(), value.to_string())))
+                    .collect::<Result<_, _>>()?;
+            }
+            ("--think", Some(value)) => options.think_millis = Some(parse_millis(name, value)?),
+            ("--save", Some(value)) => options.save = Some(value.to_string()),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    check_otto(&options.board)?;
+    Ok(options)
+}
+
+fn parse_engine(args: &[String]) -> Result<EngineOptions, ArgError> {
+    let mut options = EngineOptions { board: BoardOptions::default(), level: Difficulty::Hard };
+    for arg in parse_options(args)? {
+        let (name, value) = match &arg {
+            Arg::Option(name, value) => (name.as_str(), value.as_deref()),
+            Arg::Positional(_) => return Err(arg.unexpected()),
+        };
+        if board_option(&mut options.board, name, value)? {
+            continue;
+        }
+        match (name, value) {
+            ("--level", Some(value)) => {
+                options.level = parse_level(value).ok_or_else(|| ArgError::BadValue(name.to_string(), value.to_string()))?;
+            }
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    check_otto(&options.board)?;
+    Ok(options)
+}
+
+// reads a level and settings like hard,depth=6,center=4
+fn parse_setup(name: &str, value: &str) -> Result<SetupSpec, ArgError> {
+    let bad = || ArgError::BadValue(name.to_string(), value.to_string());
+    let mut setup = SetupSpec::default();
+    for part in value.split(',') {
+        match part.split_once('=') {
+            None => setup.level = parse_level(part).ok_or_else(bad)?,
+            Some(("depth", depth)) => setup.depth = Some(depth.parse().map_err(|_| bad())?),
+            Some(("iterations", iterations)) => setup.iterations = Some(iterations.parse().map_err(|_| bad())?),
+            Some(("rollout", "random")) => setup.rollout = Some(Rollout::Random),
+            Some(("rollout", "heuristic")) => setup.rollout = Some(Rollout::Heuristic),
+            Some(("rollout", _)) => return Err(bad()),
+            Some((weight, value)) => setup.weights.push((weight.to_string(), value.parse().map_err(|_| bad())?)),
+        }
+    }
+    Ok(setup)
+}
+
+fn parse_tournament(args: &[String]) -> Result<TournamentOptions, ArgError> {
+    let mut options = TournamentOptions {
+        board: BoardOptions::default(),
+        first: SetupSpec::default(),
+        second: SetupSpec::default(),
+        games: 20,
+        openings: 2,
+        think_millis: None,
+    };
+    for arg in parse_options(args)? {
+        let (name, value) = match &arg {
+            Arg::Option(name, value) => (name.as_str(), value.as_deref()),
+            Arg::Positional(_) => return Err(arg.unexpected()),
+        };
+        if board_option(&mut options.board, name, value)? {
+            continue;
+        }
+        let bad = || ArgError::BadValue(name.to_string(), value.unwrap_or("").to_string());
+        match (name, value) {
+            ("--first", Some(value)) => options.first = parse_setup(name, value)?,
+            ("--second", Some(value)) => options.second = parse_setup(name, value)?,
+            ("--games", Some(value)) => options.games = value.parse().map_err(|_| bad())?,
+            ("--openings", Some(value)) => options.openings = value.parse().map_err(|_| bad())?,
+            ("--think", Some(value)) => options.think_millis = Some(parse_millis(name, value)?),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    check_otto(&options.board)?;
+    Ok(options)
+}
+
+fn parse_ratings(args: &[String]) -> Result<RatingsOptions, ArgError> {
+    let mut options = RatingsOptions { ledger: DEFAULT_LEDGER.to_string(), system: RatingSystem::Elo, add: None };
+    let mut positional = Vec::new();
+    for arg in parse_options(args)? {
+        let (name, value) = match arg {
+            Arg::Option(name, Some(value)) => (name, value),
+            Arg::Option(..) => return Err(arg.unexpected()),
+            Arg::Positional(arg) => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        match name.as_str() {
+            "--ledger" => options.ledger = value,
+            "--system" => {
+                options.system = match value.as_str() {
+                    "elo" => RatingSystem::Elo,
+                    "glicko2" => RatingSystem::Glicko2,
+                    _ => return Err(ArgError::BadValue(name, value)),
+                }
+            }
+            _ => return Err(ArgError::UnknownOption(name)),
+        }
+    }
+    let mut positional = positional.into_iter();
+    match positional.next().as_deref() {
+        None => (),
+        Some("add") => {
+            let file = positional.next().ok_or(ArgError::MissingArgument("a saved game"))?;
+            options.add = Some((file, positional.collect()));
+        }
+        Some(other) => return Err(ArgError::Unexpected(other.to_string())),
+    }
+    Ok(options)
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::UnknownSubcommand(name) => write!(f, "there's no {} command", name),
+            ArgError::UnknownOption(name) => write!(f, "that command doesn't take {}", name),
+            ArgError::MissingValue(name) => write!(f, "{} needs a value after it", name),
+            ArgError::BadValue(name, value) => write!(f, "{} can't be {}", name, value),
+            ArgError::NotForOtto(name) => write!(f, "{} is only for Connect 4", name),
+            ArgError::MissingArgument(what) => write!(f, "that command needs {}", what),
+            ArgError::Unexpected(arg) => write!(f, "didn't expect {}", arg),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Subcommand, ArgError> {
+        parse(&line.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn nothing_or_help_is_help() {
+        assert_eq!(parse_line(""), Ok(Subcommand::Help));
+        for help in ["help", "--help", "-h"] {
+            assert_eq!(parse_line(help), Ok(Subcommand::Help));
+        }
+        assert_eq!(parse_line("solve"), Err(ArgError::UnknownSubcommand("solve".to_string())));
+    }
+
+    #[test]
+    fn play_reads_the_board_and_the_opponent() {
+        let options = match parse_line("play --rules popout --size 8x7 --win 5 --misere --vs mcts --as o --think 300 --names ann --save game.txt") {
+            Ok(Subcommand::Play(options)) => options,
+            other => panic!("{:?}", other),
+        };
+        let board = BoardOptions { size: Size::Custom(8, 7), variant: Variant::PopOut, win_length: Some(5), misere: true, ..BoardOptions::default() };
+        assert_eq!(options.board, board);
+        assert_eq!((options.vs, options.side, options.think_millis), (Some(Difficulty::Mcts), Some('O'), Some(300)));
+        assert_eq!((options.names, options.save, options.ledger), (vec!["ann".to_string()], Some("game.txt".to_string()), DEFAULT_LEDGER.to_string()));
+
+        match parse_line("play --vs human --no-gravity --players 3") {
+            Ok(Subcommand::Play(options)) => {
+                assert_eq!(options.vs, None);
+                assert!(!options.board.gravity);
+                assert_eq!(options.board.players, Some(3));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn analyze_joins_a_position_split_at_its_space() {
+        let expected = AnalyzeOptions {
+            game: GameKind::Connect4,
+            position: "7/7/7/7/7/3X3 O".to_string(),
+            level: Some(Difficulty::Easy),
+            think_millis: None,
+        };
+        assert_eq!(parse_line("analyze 7/7/7/7/7/3X3 O --level easy"), Ok(Subcommand::Analyze(expected)));
+        assert_eq!(parse_line("analyze --game otto"), Err(ArgError::MissingArgument("a position")));
+        assert_eq!(parse_line("analyze 3 --misere"), Err(ArgError::UnknownOption("--misere".to_string())));
+    }
+
+    #[test]
+    fn selfplay_takes_a_level_per_player() {
+        match parse_line("selfplay --game otto --size large --levels easy,hard") {
+            Ok(Subcommand::Selfplay(options)) => {
+                assert_eq!((options.board.game, options.board.size), (GameKind::Otto, Size::Large));
+                assert_eq!(options.levels, [Difficulty::Easy, Difficulty::Hard]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(parse_line("selfplay --levels easy,best"), Err(ArgError::BadValue("--levels".to_string(), "easy,best".to_string())));
+    }
+
+    #[test]
+    fn replay_takes_one_file_and_engine_a_board() {
+        assert_eq!(parse_line("replay game.txt"), Ok(Subcommand::Replay("game.txt".to_string())));
+        assert_eq!(parse_line("replay"), Err(ArgError::MissingArgument("the saved game's file name")));
+        assert_eq!(parse_line("replay a.txt b.txt"), Err(ArgError::Unexpected("b.txt".to_string())));
+        let expected = EngineOptions { board: BoardOptions { variant: Variant::Pop10, ..BoardOptions::default() }, level: Difficulty::Perfect };
+        assert_eq!(parse_line("engine --rules pop10 --level perfect"), Ok(Subcommand::Engine(expected)));
+    }
+
+    #[test]
+    fn tournament_reads_each_setup() {
+        let options = match parse_line("tournament --first hard,depth=6,center=4 --second mcts,iterations=500,rollout=heuristic --games 4 --openings 0") {
+            Ok(Subcommand::Tournament(options)) => options,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(options.first, SetupSpec { depth: Some(6), weights: vec![("center".to_string(), 4)], ..SetupSpec::default() });
+        let second = SetupSpec { level: Difficulty::Mcts, iterations: Some(500), rollout: Some(Rollout::Heuristic), ..SetupSpec::default() };
+        assert_eq!((options.second, options.games, options.openings), (second, 4, 0));
+        assert_eq!(parse_line("tournament --first hard,rollout=smart"), Err(ArgError::BadValue("--first".to_string(), "hard,rollout=smart".to_string())));
+    }
+
+    #[test]
+    fn ratings_shows_or_adds() {
+        let expected = RatingsOptions { ledger: "club.json".to_string(), system: RatingSystem::Glicko2, add: None };
+        assert_eq!(parse_line("ratings --system glicko2 --ledger club.json"), Ok(Subcommand::Ratings(expected)));
+        match parse_line("ratings add game.txt ann bob") {
+            Ok(Subcommand::Ratings(options)) => assert_eq!(options.add, Some(("game.txt".to_string(), vec!["ann".to_string(), "bob".to_string()]))),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(parse_line("ratings add"), Err(ArgError::MissingArgument("a saved game")));
+        assert_eq!(parse_line("ratings show"), Err(ArgError::Unexpected("show".to_string())));
+    }
+
+    #[test]
+    fn bad_options_are_refused() {
+        assert_eq!(parse_line("play --think"), Err(ArgError::MissingValue("--think".to_string())));
+        assert_eq!(parse_line("play --think 0"), Err(ArgError::BadValue("--think".to_string(), "0".to_string())));
+        assert_eq!(parse_line("play --size 7by6"), Err(ArgError::BadValue("--size".to_string(), "7by6".to_string())));
+        assert_eq!(parse_line("play --vs grandmaster"), Err(ArgError::BadValue("--vs".to_string(), "grandmaster".to_string())));
+        assert_eq!(parse_line("play --as XO"), Err(ArgError::BadValue("--as".to_string(), "XO".to_string())));
+        assert_eq!(parse_line("play --colour red"), Err(ArgError::UnknownOption("--colour".to_string())));
+        assert_eq!(parse_line("play extra"), Err(ArgError::Unexpected("extra".to_string())));
+        assert_eq!(parse_line("engine --game otto --win 5"), Err(ArgError::NotForOtto("--win".to_string())));
+        assert_eq!(parse_line("selfplay --game otto --rules popout"), Err(ArgError::NotForOtto("--rules".to_string())));
+        assert_eq!(parse_line("ratings --system trueskill"), Err(ArgError::BadValue("--system".to_string(), "trueskill".to_string())));
+    }
+}
@@ -1,10 +1,18 @@
+use std::convert::TryFrom;
+use std::fmt;
+use crate::game::Outcome;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Difficulty {
     Easy,
     Hard,
+    Perfect,
+    /// Monte Carlo tree search instead of minimax
+    Mcts,
 }
 
 #[wasm_bindgen]
@@ -12,4 +20,353 @@ pub enum Difficulty {
 pub enum BoardSize {
     Standard,
     Large,
-}
\ No newline at end of file
+}
+
+/// Fewest columns and rows either board can have, any fewer and nobody can
+/// line up four
+pub const MIN_DIMENSION: u32 = 4;
+/// Most columns and rows either board can have. Connect 4 keeps `height + 1`
+/// bits per column in a `Bitboard`, and the Zobrist keys cover 128 cells.
+pub const MAX_WIDTH: u32 = 12;
+pub const MAX_HEIGHT: u32 = 9;
+
+/// Fewest and most players a Connect 4 board can seat. TOOT and OTTO, PopOut
+/// and Pop 10 are always two-player.
+pub const MIN_PLAYERS: u32 = 2;
+pub const MAX_PLAYERS: u32 = 4;
+
+/// A side in either game. In Connect 4 `One` drops the X discs and moves
+/// first, `Two` drops the O discs, and with more players `Three` and `Four`
+/// drop Y and Z discs in that order. In TOOT and OTTO `One` is Otto and `Two`
+/// is Toot.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Player {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+/// A letter in TOOT and OTTO
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Token {
+    T,
+    O,
+}
+
+/// What's in a square of either board
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Cell {
+    Empty,
+    X,
+    O,
+    T,
+    Y,
+    Z,
+}
+
+/// `GameOutcome` can't cross into JS because `Win` carries the winner, the
+/// boards export this and a separate `winner()` instead
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    InProgress,
+    Win,
+    Draw,
+}
+
+pub type GameOutcome = Outcome<Player>;
+
+/// How a move changes the board. Only Connect 4 in PopOut and Pop 10 ever
+/// pops, and only boards without gravity place.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MoveKind {
+    /// a disc or token goes in at the top and falls
+    Drop,
+    /// the mover's disc comes out of the bottom and the rest fall one row
+    Pop,
+    /// a disc or token goes straight onto a chosen empty cell
+    Place,
+}
+
+/// Where a move landed and how the game stands after it
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveOutcome {
+    /// 0 is the top row
+    pub row: u32,
+    pub col: u32,
+    pub status: GameStatus,
+}
+
+/// One move in a board's history, with what the board's last_* getters
+/// returned before it so undo can put them back
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub col: u32,
+    /// 0 is the top row
+    pub row: u32,
+    /// only set in TOOT and OTTO
+    pub token: Option<Token>,
+    pub kind: MoveKind,
+    pub player: Player,
+    pub prior_row: Option<u32>,
+    pub prior_col: Option<u32>,
+    pub prior_player: Option<Player>,
+}
+
+/// Why a move was rejected. In JS it's thrown as an `Error` carrying the
+/// message below.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    ColumnFull,
+    ColumnOutOfRange,
+    InvalidToken,
+    OutOfTokens,
+    GameAlreadyOver,
+    WrongPlayerToMove,
+    PopNotAllowed,
+    NotYourDisc,
+    FillLowestRowFirst,
+    MustPop,
+    PickAnotherColumn,
+    CellOutOfRange,
+    CellTaken,
+    PlacementNotAllowed,
+    DropNotAllowed,
+    GameAlreadyStarted,
+    PlayersOutOfRange,
+    InvalidSymbol,
+}
+
+/// Shortest line Connect-N can be played to
+pub const MIN_WIN_LENGTH: u32 = 3;
+
+/// Why a board can't be made `with_dimensions`. In JS it's thrown as an
+/// `Error` like `MoveError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DimensionError {
+    TooSmall,
+    TooLarge,
+    WinLengthOutOfRange,
+    PlayersOutOfRange,
+}
+
+/// Why `from_notation` couldn't read a board. In JS it's thrown as an
+/// `Error` like `MoveError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotationError {
+    Unreadable,
+    RaggedRows,
+    Dimensions(DimensionError),
+    /// the position is read onto a board of another size
+    WrongSize,
+    FloatingPiece,
+    ImpossibleCounts,
+    WrongSideToMove,
+    /// the move, counting from 1, and why it couldn't be played
+    IllegalMove(u32, MoveError),
+}
+
+/// Checks `width` and `height` against the limits above
+pub fn check_dimensions(width: u32, height: u32) -> Result<(), DimensionError> {
+    if width < MIN_DIMENSION || height < MIN_DIMENSION {
+        Err(DimensionError::TooSmall)
+    } else if width > MAX_WIDTH || height > MAX_HEIGHT {
+        Err(DimensionError::TooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+impl Player {
+    /// Every seat in turn order
+    pub const ALL: [Player; MAX_PLAYERS as usize] = [Player::One, Player::Two, Player::Three, Player::Four];
+
+    /// The opponent in a two-player game
+    pub fn other(self) -> Player {
+        self.next(2)
+    }
+
+    /// Whose turn comes after this player's when `players` are playing
+    pub fn next(self, players: u32) -> Player {
+        Player::ALL[(self as usize + 1) % players as usize]
+    }
+
+    /// The disc this player drops in Connect 4
+    pub fn disc(self) -> Cell {
+        match self {
+            Player::One => Cell::X,
+            Player::Two => Cell::O,
+            Player::Three => Cell::Y,
+            Player::Four => Cell::Z,
+        }
+    }
+
+    /// The word this player spells in TOOT and OTTO
+    pub fn word(self) -> &'static str {
+        match self {
+            Player::One => "Otto",
+            Player::Two => "Toot",
+            Player::Three | Player::Four => unreachable!("TOOT and OTTO is a two-player game"),
+        }
+    }
+
+    /// The letter at both ends of this player's word
+    pub fn letter(self) -> Token {
+        match self {
+            Player::One => Token::O,
+            Player::Two => Token::T,
+            Player::Three | Player::Four => unreachable!("TOOT and OTTO is a two-player game"),
+        }
+    }
+}
+
+/// Reads a token typed as T or O, in either case
+impl TryFrom<char> for Token {
+    type Error = MoveError;
+
+    fn try_from(c: char) -> Result<Token, MoveError> {
+        match c.to_ascii_uppercase() {
+            'T' => Ok(Token::T),
+            'O' => Ok(Token::O),
+            _ => Err(MoveError::InvalidToken),
+        }
+    }
+}
+
+impl From<Token> for Cell {
+    fn from(token: Token) -> Cell {
+        match token {
+            Token::T => Cell::T,
+            Token::O => Cell::O,
+        }
+    }
+}
+
+impl Cell {
+    pub fn symbol(self) -> char {
+        match self {
+            Cell::Empty => ' ',
+            Cell::X => 'X',
+            Cell::O => 'O',
+            Cell::T => 'T',
+            Cell::Y => 'Y',
+            Cell::Z => 'Z',
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl From<GameOutcome> for GameStatus {
+    fn from(outcome: GameOutcome) -> GameStatus {
+        match outcome {
+            Outcome::InProgress => GameStatus::InProgress,
+            Outcome::Win(_) => GameStatus::Win,
+            Outcome::Draw => GameStatus::Draw,
+        }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            MoveError::ColumnFull => "that column is full",
+            MoveError::ColumnOutOfRange => "there's no such column",
+            MoveError::InvalidToken => "tokens are T or O",
+            MoveError::OutOfTokens => "there are no tiles of that letter left",
+            MoveError::GameAlreadyOver => "the game is already over",
+            MoveError::WrongPlayerToMove => "it's the other player's turn",
+            MoveError::PopNotAllowed => "discs can't be popped now",
+            MoveError::FillLowestRowFirst => "the lowest row with gaps has to be filled first",
+            MoveError::MustPop => "you have to pop one of your discs",
+            MoveError::PickAnotherColumn => "the disc has to go back in another column",
+            MoveError::CellOutOfRange => "there's no such cell",
+            MoveError::CellTaken => "that cell is taken",
+            MoveError::PlacementNotAllowed => "pieces drop into columns on this board",
+            MoveError::DropNotAllowed => "pieces go on a chosen cell on this board",
+            MoveError::NotYourDisc => "you can only pop your own disc from the bottom",
+            MoveError::GameAlreadyStarted => "that can only be changed before the first piece is down",
+            MoveError::PlayersOutOfRange => return write!(f, "{}", DimensionError::PlayersOutOfRange),
+            MoveError::InvalidSymbol => "each player needs a symbol of their own that isn't a space, a digit or /",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl From<MoveError> for JsValue {
+    fn from(error: MoveError) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
+
+impl fmt::Display for DimensionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problem = match self {
+            DimensionError::TooSmall => "too small",
+            DimensionError::TooLarge => "too large",
+            DimensionError::WinLengthOutOfRange => {
+                return write!(f, "lines must be at least {} long and fit on the board", MIN_WIN_LENGTH);
+            }
+            DimensionError::PlayersOutOfRange => {
+                return write!(f, "Connect 4 is played by {} to {} players, PopOut and Pop 10 by two", MIN_PLAYERS, MAX_PLAYERS);
+            }
+        };
+        write!(f, "that board is {}, boards are {} to {} columns wide and {} to {} rows high",
+            problem, MIN_DIMENSION, MAX_WIDTH, MIN_DIMENSION, MAX_HEIGHT)
+    }
+}
+
+impl std::error::Error for DimensionError {}
+
+impl From<DimensionError> for JsValue {
+    fn from(error: DimensionError) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
+
+impl From<DimensionError> for NotationError {
+    fn from(error: DimensionError) -> NotationError {
+        NotationError::Dimensions(error)
+    }
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Unreadable => write!(f, "that isn't a position or a list of moves"),
+            NotationError::RaggedRows => write!(f, "every row has to be as wide as the board"),
+            NotationError::Dimensions(error) => write!(f, "{}", error),
+            NotationError::WrongSize => write!(f, "the position isn't the size of the board being played"),
+            NotationError::FloatingPiece => write!(f, "a piece is floating over an empty cell"),
+            NotationError::ImpossibleCounts => write!(f, "no game ends up with those numbers of pieces"),
+            NotationError::WrongSideToMove => write!(f, "it can't be that player's turn with those pieces down"),
+            NotationError::IllegalMove(number, error) => write!(f, "move {} can't be played: {}", number, error),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl From<NotationError> for JsValue {
+    fn from(error: NotationError) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
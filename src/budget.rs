@@ -0,0 +1,61 @@
+//! Time and node limits for the engines' iterative deepening
+//!
+//! Reading the clock on every node would cost more than the node itself, so
+//! the deadline is only checked every `CHECK_INTERVAL` nodes. In the browser
+//! `std::time` isn't available and the time comes from `Date.now()`.
+
+const CHECK_INTERVAL: u64 = 1024;
+
+pub struct Budget {
+    deadline: Option<f64>,
+    max_nodes: Option<u64>,
+    nodes: u64,
+    exhausted: bool,
+}
+
+impl Budget {
+    /// No limit at all, the search stops at its depth
+    pub fn unlimited() -> Budget {
+        Budget { deadline: None, max_nodes: None, nodes: 0, exhausted: false }
+    }
+
+    /// Runs out `millis` milliseconds from now
+    pub fn millis(millis: u32) -> Budget {
+        Budget { deadline: Some(now_millis() + millis as f64), ..Budget::unlimited() }
+    }
+
+    /// Runs out after `nodes` positions have been searched
+    pub fn nodes(nodes: u64) -> Budget {
+        Budget { max_nodes: Some(nodes), ..Budget::unlimited() }
+    }
+
+    /// Counts one searched position, returns true once the budget has run out
+    pub fn tick(&mut self) -> bool {
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|max| self.nodes >= max) {
+            self.exhausted = true;
+        }
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            if let Some(deadline) = self.deadline {
+                self.exhausted |= now_millis() >= deadline;
+            }
+        }
+        self.exhausted
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+
+#[cfg(target_arch = "wasm32")]
+fn now_millis() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_millis() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64() * 1000.0
+}
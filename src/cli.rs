@@ -1,7 +1,157 @@
+use std::cmp;
+use std::fs;
 use std::io::stdin;
+use crate::args::{self, AnalyzeOptions, BoardOptions, EngineOptions, GameKind, PlayOptions, RatingsOptions, SelfplayOptions, SetupSpec, Size, Subcommand, TournamentOptions};
+use crate::engine;
 use crate::connect4::*;
-use crate::board::{BoardSize, Difficulty};
+use crate::board::{BoardSize, DimensionError, Difficulty, NotationError, Player, MAX_PLAYERS};
+use crate::game::{Game, Outcome};
 use crate::otto::*;
+use crate::ratings::{Ledger, RatingSystem};
+use crate::ottobot::OttoBot;
+use crate::record::{self, GameRecord};
+use crate::search::Bot;
+use crate::solver::{self, Verdict};
+use crate::tournament::{self, EngineSetup, GameResult, TournamentGame};
+
+/// What the person at the keyboard asked for on their turn
+pub enum Command<M> {
+    Play(M),
+    Undo,
+    Redo,
+}
+
+/// The parts of a game host_game can't do generically
+pub trait CliGame: Game {
+    /// Asks the person whose turn it is for a legal move, or to undo or redo.
+    /// n prints the position's notation and asks again.
+    fn read_move(&self) -> Command<Self::Move>;
+
+    /// What the players are trying to do, printed before the first move
+    fn objective(&self) -> String;
+
+    /// Prints the final board and who won
+    fn print_congrats(&self);
+
+    /// Plays `mv` for good, so moves taken back can't be redone any more
+    fn commit(&mut self, mv: Self::Move);
+
+    /// Takes back the last move, false if there wasn't one
+    fn take_back(&mut self) -> bool;
+
+    /// Plays the last move taken back again, false if there wasn't one
+    fn replay(&mut self) -> bool;
+
+    /// The moves made so far, oldest first, written as game records write them
+    fn moves(&self) -> Vec<String>;
+
+    /// The record tags naming the game and its rules, and the Start tag with
+    /// the position in notation
+    fn record_tags(&self) -> Vec<(&'static str, String)>;
+
+    /// Every player, in turn order
+    fn seats(&self) -> Vec<Self::Player>;
+
+    /// What `player` is called in game records
+    fn name(&self, player: Self::Player) -> String;
+}
+
+/// The computer's side of a host_game
+pub struct Computer<'a, G: Game> {
+    pub plays: G::Player,
+    pub bot: &'a mut dyn Bot<G>,
+    // think this long per move instead of searching to the bot's depth
+    pub think_millis: Option<u32>,
+}
+
+/// Plays a game in the terminal from its current position and returns its
+/// record. Every side none of the `computers` plays is read from stdin. Undo
+/// and redo skip over the computers' moves, so it's a person's turn
+/// afterwards.
+pub fn host_game<G: CliGame>(game: &mut G, mut computers: Vec<Computer<G>>) -> GameRecord {
+    let computer_plays: Vec<G::Player> = computers.iter().map(|ai| ai.plays).collect();
+    let is_computer = |player| computer_plays.contains(&player);
+
+    let mut record = GameRecord::new();
+    for (name, value) in game.record_tags() {
+        record.set_tag(name, value);
+    }
+    let players: Vec<String> = game.seats().into_iter()
+        .map(|seat| format!("{}: {}", game.name(seat), if is_computer(seat) { "Computer" } else { "Person" }))
+        .collect();
+    record.set_tag("Players", players.join(", "));
+    if let Some(ai) = computers.first() {
+        let difficulty = match ai.think_millis {
+            Some(millis) => format!("{:?}, {} ms a move", ai.bot.difficulty(), millis),
+            None => format!("{:?}", ai.bot.difficulty()),
+        };
+        record.set_tag("Difficulty", difficulty);
+    }
+    record.set_tag("Date", record::today());
+    let start = game.moves().len();
+
+    println!("{}", game.objective());
+    println!("Type u to take back a move, r to play it again or n to see the position's notation.\n");
+    while game.outcome() == Outcome::InProgress {
+        println!("{}", game);
+        let side = game.side_to_move();
+        let mv = if let Some(ai) = computers.iter_mut().find(|ai| ai.plays == side) {
+            println!("AI is thinking...");
+            match ai.think_millis {
+                Some(millis) => ai.bot.choose_move_with_budget(game, millis),
+                None => ai.bot.choose_move(game),
+            }
+        } else {
+            match game.read_move() {
+                Command::Play(mv) => mv,
+                Command::Undo => {
+                    while game.take_back() && is_computer(game.side_to_move()) {}
+                    continue;
+                }
+                Command::Redo => {
+                    while game.replay() && is_computer(game.side_to_move()) && game.outcome() == Outcome::InProgress {}
+                    continue;
+                }
+            }
+        };
+        game.commit(mv);
+    }
+    game.print_congrats();
+
+    let result = match game.outcome() {
+        Outcome::Win(player) => format!("{} wins", game.name(player)),
+        Outcome::Draw => "Draw".to_string(),
+        Outcome::InProgress => "*".to_string(),
+    };
+    record.set_tag("Result", result);
+    let mut moves = game.moves();
+    record.set_moves(moves.split_off(cmp::min(start, moves.len())));
+    record
+}
+
+/// Shows a recorded game one position at a time, from the start position to
+/// the last move
+pub fn replay_game<G: CliGame>(record: &GameRecord, game: Result<G, NotationError>) {
+    let mut game = match game {
+        Ok(game) => game,
+        Err(error) => {
+            println!("Can't replay that game: {}", error);
+            return;
+        }
+    };
+    for name in ["Players", "Difficulty", "Date", "Result"] {
+        if let Some(value) = record.tag(name) {
+            println!("{}: {}", name, value);
+        }
+    }
+    println!();
+    while game.take_back() {}
+    println!("{}", game);
+    for mv in record.moves() {
+        game.replay();
+        println!("{}\n{}", mv, game);
+    }
+}
 
 
 fn get_menu_choice(menu: &str, n: u32, ret: &mut u32) {
@@ -10,17 +160,16 @@ fn get_menu_choice(menu: &str, n: u32, ret: &mut u32) {
         println!("{}\n", menu);
         let mut s: String = "".to_string();
 
-        match stdin().read_line(&mut s) {
-            Err(_) => println!("Something went wrong reading input, please try again."),
-            Ok(_) => {}
-        };
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+        }
     
         match s.trim().parse::<u32>() {
             Err(_) => println!("Please enter a valid integer.\n"),
             Ok(p) => {
                 if 1 <= p && p <= n {
                     *ret = p;
-                    println!("");
+                    println!();
                     break;
                 } else {
                     println!("Please select a valid option.\n");
@@ -40,51 +189,702 @@ fn get_continue() {
         match stdin().read_line(&mut s) {
             Err(_) => println!("Something went wrong reading input, please try again."),
             Ok(_) => {
-                println!("");
+                println!();
                 break;
             }
         };
     }
 }
 
-fn play_connect4(size: u32, mode: u32) -> String {
+fn get_think_time() -> u32 {
+    let mut millis = 0;
+    get_menu_choice("How many milliseconds may the computer think per move? (1-60000)", 60000, &mut millis);
+    millis
+}
+
+fn get_dimensions<B>(make: impl Fn(u32, u32) -> Result<B, DimensionError>) -> B {
+    // asks for a width and height until `make` accepts them
+    loop {
+        println!("Please enter the board's width and height, e.g. 8 7: ");
+        let mut s: String = "".to_string();
+
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        let numbers: Vec<u32> = s.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+        match numbers[..] {
+            [width, height] => match make(width, height) {
+                Ok(board) => return board,
+                Err(error) => println!("Can't make that board: {}\n", error),
+            },
+            _ => println!("Please enter two whole numbers.\n"),
+        }
+    }
+}
+
+fn get_misere() -> bool {
+    let mut objective = 0;
+    get_menu_choice("Please select the objective:
+1. Normal
+2. Misère (completing your line or word loses)", 2, &mut objective);
+    objective == 2
+}
+
+fn get_gravity(pieces: &str) -> bool {
+    let mut placement = 0;
+    get_menu_choice(&format!("How are {} played?
+1. Dropped into a column
+2. Placed on any empty cell", pieces), 2, &mut placement);
+    placement == 1
+}
+
+fn get_win_length(board: Connect4Board, variant: Variant) -> Connect4Board {
+    // asks how many discs in a row win on a board the size of `board` played
+    // by `variant`, four if left empty
+    loop {
+        println!("How many discs in a row win? (leave empty for 4)");
+        let mut s: String = "".to_string();
+
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        let n = if s.trim().is_empty() { Ok(4) } else { s.trim().parse::<u32>() };
+        match n {
+            Ok(n) => match Connect4Board::with_variant(board.width(), board.height(), n, variant) {
+                Ok(board) => return board,
+                Err(error) => println!("Can't play to that: {}\n", error),
+            },
+            Err(_) => println!("Please enter a valid integer.\n"),
+        }
+    }
+}
+
+fn get_players(game: &mut Connect4Board) {
+    // asks how many take turns on `game` until it can seat them
+    loop {
+        let mut players = 0;
+        get_menu_choice(&format!("How many players? (2-{})", MAX_PLAYERS), MAX_PLAYERS, &mut players);
+        match game.set_players(players) {
+            Ok(()) => return,
+            Err(error) => println!("Can't seat that many: {}\n", error),
+        }
+    }
+}
+
+fn get_symbols(game: &mut Connect4Board) {
+    // asks for a different character for each player's discs, the usual
+    // letters if left empty
+    let players = game.players() as usize;
+    loop {
+        let defaults: String = Player::ALL[..players].iter().map(|&player| game.symbol(player)).collect();
+        println!("Please enter a symbol for each player's discs, e.g. {} (leave empty for those): ", defaults);
+        let mut s: String = "".to_string();
+
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        let symbols: Vec<char> = s.trim().chars().collect();
+        if symbols.is_empty() {
+            return;
+        }
+        if symbols.len() == players {
+            match game.set_symbols(&symbols) {
+                Ok(()) => return,
+                Err(error) => println!("Can't use those: {}\n", error),
+            }
+            continue;
+        }
+        println!("Please enter {} different characters.\n", players);
+    }
+}
+
+fn get_people(players: u32) -> u32 {
+    // the people take the first seats and the computer the rest
+    if players == 2 {
+        return 1;
+    }
+    let mut people = 0;
+    get_menu_choice(&format!("How many of the {} players are people? (1-{})", players, players - 1), players - 1, &mut people);
+    people
+}
+
+fn play_connect4(size: u32, mode: u32) -> Option<GameRecord> {
     println!("Starting Connect4....");
-    let size = match size {
-        1 => BoardSize::Standard,
-        2 => BoardSize::Large,
-        _ => BoardSize::Standard,
+    let game = match size {
+        2 => Connect4Board::new(BoardSize::Large),
+        3 => get_dimensions(Connect4Board::with_dimensions),
+        _ => Connect4Board::new(BoardSize::Standard),
+    };
+
+    let mut rules = 0;
+    get_menu_choice("Please select the rules:
+1. Classic
+2. PopOut (pop your own discs out of the bottom)
+3. Five-in-a-Row (9x6 board with full outer columns)
+4. Pop 10 (fill the board, then keep ten discs popped out of your lines)", 4, &mut rules);
+    let variant = match rules {
+        2 => Variant::PopOut,
+        3 => Variant::FiveInARow,
+        4 => Variant::Pop10,
+        _ => Variant::Classic,
+    };
+
+    let mut game = match variant {
+        Variant::FiveInARow => Connect4Board::five_in_a_row(),
+        _ => get_win_length(game, variant),
     };
+    // popping needs discs stacked in columns, so those variants keep gravity
+    // and two players
+    if variant == Variant::Classic || variant == Variant::FiveInARow {
+        get_players(&mut game);
+        game.set_gravity(get_gravity("discs"));
+    }
+    if game.players() == 2 {
+        game.set_misere(get_misere());
+    } else {
+        get_symbols(&mut game);
+    }
+
+    let people = if mode == 1 { game.players() } else { get_people(game.players()) };
+    match mode {
+        1 => Some(game.host_game()),
+        2 => Some(game.host_game_AI(Difficulty::Easy, people, None)),
+        3 => Some(game.host_game_AI(Difficulty::Hard, people, None)),
+        4 => Some(game.host_game_AI(Difficulty::Perfect, people, None)),
+        5 => Some(game.host_game_AI(Difficulty::Mcts, people, None)),
+        6 => Some(game.host_game_AI(Difficulty::Hard, people, Some(get_think_time()))),
+        _ => None,
+    }
+}
 
-    let mut game = Connect4Board::new(size);
+fn solve_connect4() {
+    // reads the moves played so far, X first, or the position in notation
+    // and prints the exact result
+    let (board, player) = loop {
+        println!("Please enter the columns played so far, e.g. 33443322, or a position, e.g. 7/7/3O3/3X3/2OOO2/2XXX2 X, with at least {} discs down: ", SOLVER_MIN_DISCS);
+        let mut s: String = "".to_string();
 
-    let result = match mode {
-        1 => game.host_game(),
-        2 => game.host_game_AI(Difficulty::Easy),
-        3 => game.host_game_AI(Difficulty::Hard),
-        _ => "".to_string(),
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        match Connect4Board::from_notation(&s) {
+            // earlier positions take far too long to solve
+            Ok(board) if board.occupied().count_ones() < SOLVER_MIN_DISCS && !board.is_terminal() => {
+                println!("Can't solve that yet: play until there are at least {} discs down.\n", SOLVER_MIN_DISCS);
+            }
+            Ok(board) => match solver::check_solvable(&board) {
+                Ok(()) => {
+                    let player = board.side_to_move();
+                    break (board, player);
+                }
+                Err(error) => println!("Can't solve that: {}.\n", error),
+            },
+            Err(error) => println!("Can't read that: {}\n", error),
+        }
     };
 
-    result
+    println!("{}", board);
+    print_solution(&board, player);
+}
+
+fn print_solution(board: &Connect4Board, player: Player) {
+    // solves a standard board and prints the result for `player`, who's to move
+    println!("Solving...");
+    let solution = match solver::solve(board) {
+        Ok(solution) => solution,
+        Err(error) => {
+            println!("Can't solve that: {}.", error);
+            return;
+        }
+    };
+    match solution.verdict() {
+        Verdict::Win => println!("{} to move wins in {} moves.", player.disc(), solution.distance()),
+        Verdict::Loss => println!("{} to move loses in {} moves.", player.disc(), solution.distance()),
+        Verdict::Draw => println!("Perfect play ends in a draw in {} moves.", solution.distance()),
+    }
+    if let Some(col) = solution.best_move() {
+        println!("Best move for {}: column {}", player.disc(), col);
+    }
 }
 
-fn play_otto(size: u32, mode: u32, tok: char) -> String {
+fn play_otto(size: u32, mode: u32, player: Player) -> Option<GameRecord> {
     println!("Starting Toot and Otto....");
-    let size = match size {
-        1 => BoardSize::Standard,
-        2 => BoardSize::Large,
-        _ => BoardSize::Standard,
+    let mut game = match size {
+        2 => TootOttoBoard::new(BoardSize::Large),
+        3 => get_dimensions(TootOttoBoard::with_dimensions),
+        _ => TootOttoBoard::new(BoardSize::Standard),
     };
+    game.set_gravity(get_gravity("tokens"));
+    game.set_misere(get_misere());
 
-    let mut game = TootOttoBoard::new(size);
+    match mode {
+        1 => Some(game.host_game()),
+        2 => Some(game.host_game_AI(Difficulty::Easy, player, None)),
+        3 => Some(game.host_game_AI(Difficulty::Hard, player, None)),
+        4 => Some(game.host_game_AI(Difficulty::Mcts, player, None)),
+        5 => Some(game.host_game_AI(Difficulty::Hard, player, Some(get_think_time()))),
+        _ => None,
+    }
+}
 
-    let result = match mode {
-        1 => game.host_game(),
-        2 => game.host_game_AI(Difficulty::Easy, tok),
-        3 => game.host_game_AI(Difficulty::Hard, tok),
-        _ => "".to_string(),
+fn save_record(record: &GameRecord) {
+    // offers to write the game's record to a file
+    loop {
+        println!("Please enter a file name to save the game record to (leave empty to skip): ");
+        let mut s: String = "".to_string();
+
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        if s.trim().is_empty() {
+            return;
+        }
+        match fs::write(s.trim(), record.to_string()) {
+            Ok(()) => {
+                println!("Saved the game to {}.\n", s.trim());
+                return;
+            }
+            Err(error) => println!("Can't save there: {}\n", error),
+        }
+    }
+}
+
+fn replay_saved_game() {
+    // reads a record saved after a game and shows it move by move
+    let record = loop {
+        println!("Please enter the file name of a saved game: ");
+        let mut s: String = "".to_string();
+
+        if stdin().read_line(&mut s).is_err() {
+            println!("Something went wrong reading input, please try again.");
+            continue;
+        }
+
+        match read_record(s.trim()) {
+            Ok(record) => break record,
+            Err(error) => println!("{}\n", error),
+        }
+    };
+    replay_record(&record);
+}
+
+fn read_record(file: &str) -> Result<GameRecord, String> {
+    let text = fs::read_to_string(file).map_err(|error| format!("Can't open that file: {}", error))?;
+    GameRecord::parse(&text).map_err(|error| format!("Can't read that game: {}", error))
+}
+
+fn replay_record(record: &GameRecord) {
+    match record.tag("Game") {
+        Some("Connect 4") => replay_game(record, Connect4Board::from_record(record)),
+        Some("TOOT and OTTO") => replay_game(record, TootOttoBoard::from_record(record)),
+        _ => println!("That isn't a record of a Connect 4 or TOOT and OTTO game."),
+    }
+}
+
+fn make_connect4(options: &BoardOptions) -> Result<Connect4Board, DimensionError> {
+    let mut game = match options.variant {
+        Variant::FiveInARow => Connect4Board::five_in_a_row(),
+        variant => {
+            let size = match options.size {
+                Size::Standard => Connect4Board::new(BoardSize::Standard),
+                Size::Large => Connect4Board::new(BoardSize::Large),
+                Size::Custom(width, height) => Connect4Board::with_dimensions(width, height)?,
+            };
+            Connect4Board::with_variant(size.width(), size.height(), options.win_length.unwrap_or(4), variant)?
+        }
+    };
+    if let Some(players) = options.players {
+        game.set_players(players).map_err(|_| DimensionError::PlayersOutOfRange)?;
+    }
+    game.set_gravity(options.gravity);
+    game.set_misere(options.misere);
+    Ok(game)
+}
+
+fn make_otto(options: &BoardOptions) -> Result<TootOttoBoard, DimensionError> {
+    let mut game = match options.size {
+        Size::Standard => TootOttoBoard::new(BoardSize::Standard),
+        Size::Large => TootOttoBoard::new(BoardSize::Large),
+        Size::Custom(width, height) => TootOttoBoard::with_dimensions(width, height)?,
+    };
+    game.set_gravity(options.gravity);
+    game.set_misere(options.misere);
+    Ok(game)
+}
+
+fn write_record(record: &GameRecord, file: &Option<String>) -> i32 {
+    // saves the record if a file was given, returning the exit code
+    let file = match file {
+        Some(file) => file,
+        None => return 0,
+    };
+    match fs::write(file, record.to_string()) {
+        Ok(()) => {
+            println!("Saved the game to {}.", file);
+            0
+        }
+        Err(error) => {
+            eprintln!("Can't save there: {}", error);
+            1
+        }
+    }
+}
+
+fn command_play(options: &PlayOptions) -> i32 {
+    let record = match options.board.game {
+        GameKind::Connect4 => {
+            let mut game = match make_connect4(&options.board) {
+                Ok(game) => game,
+                Err(error) => {
+                    eprintln!("Can't make that board: {}", error);
+                    return 1;
+                }
+            };
+            let seats = game.seats();
+            let person = match options.side {
+                None => Player::One,
+                Some(symbol) => match seats.iter().find(|&&seat| game.symbol(seat) == symbol) {
+                    Some(&seat) => seat,
+                    None => {
+                        eprintln!("Nobody plays {} on that board.", symbol);
+                        return 2;
+                    }
+                },
+            };
+            match options.vs {
+                None => game.host_game(),
+                Some(difficulty) => {
+                    // every seat but the person's gets an AI of its own
+                    let seats: Vec<Player> = seats.into_iter().filter(|&seat| seat != person).collect();
+                    let mut ais: Vec<Connect4AI> = seats.iter().map(|_| Connect4AI::new(difficulty)).collect();
+                    let computers = seats.iter().zip(ais.iter_mut())
+                        .map(|(&plays, ai)| Computer { plays, bot: ai as &mut dyn Bot<Connect4Board>, think_millis: options.think_millis })
+                        .collect();
+                    host_game(&mut game, computers)
+                }
+            }
+        }
+        GameKind::Otto => {
+            let mut game = match make_otto(&options.board) {
+                Ok(game) => game,
+                Err(error) => {
+                    eprintln!("Can't make that board: {}", error);
+                    return 1;
+                }
+            };
+            let person = match options.side {
+                None | Some('O') => Player::One,
+                Some('T') => Player::Two,
+                Some(symbol) => {
+                    eprintln!("Nobody plays {} in TOOT and OTTO, play as T or O.", symbol);
+                    return 2;
+                }
+            };
+            match options.vs {
+                None => game.host_game(),
+                Some(difficulty) => game.host_game_AI(difficulty, person, options.think_millis),
+            }
+        }
     };
+    let saved = write_record(&record, &options.save);
+    if options.names.is_empty() {
+        return saved;
+    }
+
+    // the computer is rated as its level
+    let computer = match (options.vs, options.think_millis) {
+        (Some(difficulty), Some(millis)) => format!("Computer ({:?}, {} ms)", difficulty, millis),
+        (Some(difficulty), None) => format!("Computer ({:?})", difficulty),
+        (None, _) => String::new(),
+    };
+    let seats: Vec<&str> = record.tag("Players").unwrap_or("").split(", ").collect();
+    let people = seats.iter().filter(|seat| seat.ends_with("Person")).count();
+    if people != options.names.len() {
+        eprintln!("Can't rate the game: {} people played but {} names were given.", people, options.names.len());
+        return 1;
+    }
+    let mut names = options.names.iter();
+    let names: Vec<String> = seats.iter()
+        .map(|seat| if seat.ends_with("Person") { names.next().unwrap().clone() } else { computer.clone() })
+        .collect();
+    cmp::max(saved, rate_game(&record, &names, &options.ledger))
+}
+
+fn rate_game(record: &GameRecord, names: &[String], ledger: &str) -> i32 {
+    // rates the game in the ledger saved at `ledger`, returning the exit code
+    let rated = Ledger::load(ledger).and_then(|mut ratings| {
+        ratings.record_game(record, names)?;
+        ratings.save(ledger)
+    });
+    match rated {
+        Ok(()) => {
+            println!("Rated the game in {}.", ledger);
+            0
+        }
+        Err(error) => {
+            eprintln!("Can't rate the game: {}", error);
+            1
+        }
+    }
+}
+
+fn command_ratings(options: &RatingsOptions) -> i32 {
+    if let Some((file, names)) = &options.add {
+        return match read_record(file) {
+            Ok(record) => rate_game(&record, names, &options.ledger),
+            Err(error) => {
+                eprintln!("{}", error);
+                1
+            }
+        };
+    }
 
-    result
+    let ledger = match Ledger::load(&options.ledger) {
+        Ok(ledger) => ledger,
+        Err(error) => {
+            eprintln!("Can't read the ratings: {}", error);
+            return 1;
+        }
+    };
+    let mut pools = ledger.pools().peekable();
+    if pools.peek().is_none() {
+        println!("No games have been rated yet.");
+    }
+    for pool in pools {
+        println!("{}", pool);
+        for (place, (name, rating)) in (1..).zip(ledger.leaderboard(pool, options.system)) {
+            let value = match options.system {
+                RatingSystem::Elo => format!("{:.0}", rating.elo),
+                RatingSystem::Glicko2 => format!("{:.0} ± {:.0}", rating.glicko, rating.deviation),
+            };
+            println!("{:>3}. {:<24} {:>11}   {} games: {} won, {} drawn, {} lost",
+                place, name, value, rating.games(), rating.wins, rating.draws, rating.losses);
+        }
+        println!();
+    }
+    0
+}
+
+fn command_analyze(options: &AnalyzeOptions) -> i32 {
+    // prints the position, then the best move in it
+    match options.game {
+        GameKind::Connect4 => {
+            let mut board = match Connect4Board::from_notation(&options.position) {
+                Ok(board) => board,
+                Err(error) => {
+                    eprintln!("Can't read that position: {}", error);
+                    return 1;
+                }
+            };
+            let player = board.side_to_move();
+            println!("{}", board);
+            if board.outcome() != Outcome::InProgress {
+                println!("The game is over.");
+            } else if solver::check_solvable(&board).is_ok() && board.occupied().count_ones() >= SOLVER_MIN_DISCS
+                && options.level.is_none() && options.think_millis.is_none() {
+                print_solution(&board, player);
+            } else {
+                let mut ai = Connect4AI::new(options.level.unwrap_or(Difficulty::Hard));
+                let mv = match options.think_millis {
+                    Some(millis) => ai.choose_move_with_budget(&mut board, millis),
+                    None => ai.choose_move(&mut board),
+                };
+                println!("Best move for {}: {}", board.symbol(player), mv);
+            }
+        }
+        GameKind::Otto => {
+            let mut board = match TootOttoBoard::from_notation(&options.position) {
+                Ok(board) => board,
+                Err(error) => {
+                    eprintln!("Can't read that position: {}", error);
+                    return 1;
+                }
+            };
+            let player = board.side_to_move();
+            println!("{}", board);
+            if board.outcome() != Outcome::InProgress {
+                println!("The game is over.");
+            } else {
+                let mut ai = OttoBot::new(options.level.unwrap_or(Difficulty::Hard), player);
+                let mv = match options.think_millis {
+                    Some(millis) => ai.choose_move_with_budget(&mut board, millis),
+                    None => ai.choose_move(&mut board),
+                };
+                println!("Best move for {}: {}", player.word(), mv);
+            }
+        }
+    }
+    0
+}
+
+fn play_itself<G: CliGame, B: Bot<G>>(game: &mut G, ais: &mut [B], think_millis: Option<u32>) -> GameRecord {
+    // the computer takes every seat, the first of `ais` the first seat and so on
+    let seats = game.seats();
+    let levels: Vec<String> = seats.iter().zip(ais.iter())
+        .map(|(&seat, ai)| format!("{}: {:?}", game.name(seat), ai.difficulty()))
+        .collect();
+    let computers = seats.into_iter().zip(ais.iter_mut())
+        .map(|(plays, ai)| Computer { plays, bot: ai as &mut dyn Bot<G>, think_millis })
+        .collect();
+    let mut record = host_game(game, computers);
+    let mut difficulty = levels.join(", ");
+    if let Some(millis) = think_millis {
+        difficulty += &format!(", {} ms a move", millis);
+    }
+    record.set_tag("Difficulty", difficulty);
+    record
+}
+
+fn command_selfplay(options: &SelfplayOptions) -> i32 {
+    let players = match options.board.game {
+        GameKind::Connect4 => options.board.players.unwrap_or(2),
+        GameKind::Otto => 2,
+    } as usize;
+    let levels = match options.levels.len() {
+        1 => vec![options.levels[0]; players],
+        n if n == players => options.levels.clone(),
+        _ => {
+            eprintln!("Please give one level, or one for each of the {} players.", players);
+            return 2;
+        }
+    };
+
+    let record = match options.board.game {
+        GameKind::Connect4 => match make_connect4(&options.board) {
+            Ok(mut game) => {
+                let mut ais: Vec<Connect4AI> = levels.into_iter().map(Connect4AI::new).collect();
+                play_itself(&mut game, &mut ais, options.think_millis)
+            }
+            Err(error) => {
+                eprintln!("Can't make that board: {}", error);
+                return 1;
+            }
+        },
+        GameKind::Otto => match make_otto(&options.board) {
+            Ok(mut game) => {
+                let mut ais: Vec<OttoBot> = Player::ALL.iter().zip(levels).map(|(&seat, level)| OttoBot::new(level, seat)).collect();
+                play_itself(&mut game, &mut ais, options.think_millis)
+            }
+            Err(error) => {
+                eprintln!("Can't make that board: {}", error);
+                return 1;
+            }
+        },
+    };
+    println!("{}", record);
+    write_record(&record, &options.save)
+}
+
+fn command_engine(options: &EngineOptions) -> i32 {
+    let made = match options.board.game {
+        GameKind::Connect4 => make_connect4(&options.board).map(|board| engine::run_engine(board, options.level)),
+        GameKind::Otto => make_otto(&options.board).map(|board| engine::run_engine(board, options.level)),
+    };
+    match made {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("Can't make that board: {}", error);
+            1
+        }
+    }
+}
+
+fn engine_setup<G: TournamentGame>(spec: &SetupSpec) -> Result<EngineSetup<G::Weights>, String> {
+    let mut weights = G::Weights::default();
+    for (name, value) in &spec.weights {
+        if !G::set_weight(&mut weights, name, *value) {
+            return Err(format!("The heuristic has no weight called {}.", name));
+        }
+    }
+    Ok(EngineSetup { difficulty: spec.level, depth: spec.depth, iterations: spec.iterations, rollout: spec.rollout, weights })
+}
+
+fn run_tournament<G: TournamentGame>(start: &G, options: &TournamentOptions) -> i32 {
+    let setups = engine_setup::<G>(&options.first).and_then(|first| Ok((first, engine_setup::<G>(&options.second)?)));
+    let (first, second) = match setups {
+        Ok(setups) => setups,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 2;
+        }
+    };
+    if start.seats().len() != 2 {
+        eprintln!("Tournaments are for two players.");
+        return 2;
+    }
+    println!("First: {:?}", first);
+    println!("Second: {:?}", second);
+
+    let openings = tournament::balanced_openings(start, options.openings);
+    println!("Playing {} games from {} openings...", options.games, openings.len());
+    let result = tournament::play_match(start, &openings, options.games, &first, &second, options.think_millis, |number, seat, opening, result| {
+        let opening: Vec<String> = opening.iter().map(|&mv| G::write_move(mv)).collect();
+        let result = match result {
+            GameResult::Win => "first wins",
+            GameResult::Draw => "draw",
+            GameResult::Loss => "second wins",
+        };
+        println!("Game {}: first plays {}, opening {}: {}", number + 1, start.name(seat), opening.join(" "), result);
+    });
+    println!("\n{}", result);
+    0
+}
+
+fn command_tournament(options: &TournamentOptions) -> i32 {
+    let made = match options.board.game {
+        GameKind::Connect4 => make_connect4(&options.board).map(|board| run_tournament(&board, options)),
+        GameKind::Otto => make_otto(&options.board).map(|board| run_tournament(&board, options)),
+    };
+    made.unwrap_or_else(|error| {
+        eprintln!("Can't make that board: {}", error);
+        1
+    })
+}
+
+/// Runs the subcommand named by the binary's arguments and returns the exit
+/// code: 0 if it worked, 1 if it couldn't be done and 2 if the arguments were
+/// wrong
+#[allow(dead_code)]
+pub fn run_args(args: &[String]) -> i32 {
+    let subcommand = match args::parse(args) {
+        Ok(subcommand) => subcommand,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, args::USAGE);
+            return 2;
+        }
+    };
+    match subcommand {
+        Subcommand::Play(options) => command_play(&options),
+        Subcommand::Analyze(options) => command_analyze(&options),
+        Subcommand::Selfplay(options) => command_selfplay(&options),
+        Subcommand::Engine(options) => command_engine(&options),
+        Subcommand::Tournament(options) => command_tournament(&options),
+        Subcommand::Ratings(options) => command_ratings(&options),
+        Subcommand::Replay(file) => match read_record(&file) {
+            Ok(record) => {
+                replay_record(&record);
+                0
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                1
+            }
+        },
+        Subcommand::Help => {
+            println!("{}", args::USAGE);
+            0
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -96,53 +896,80 @@ pub fn run_cli() {
         get_menu_choice("Please select a game:
 1. Connect4
 2. TOOT and OTTO
-3. (Exit Program)", 3, &mut g);
+3. Solve a Connect4 position
+4. Replay a saved game
+5. (Exit Program)", 5, &mut g);
 
-        if g == 3u32 {
+        if g == 5u32 {
             break;
         }
 
+        if g == 4u32 {
+            replay_saved_game();
+            get_continue();
+            continue;
+        }
+
+        if g == 3u32 {
+            solve_connect4();
+            get_continue();
+            continue;
+        }
+
         let mut s = 0;
         get_menu_choice("Please select board size:
 1. Standard Board
-2. Larger Board", 2, &mut s);
+2. Larger Board
+3. Custom Size", 3, &mut s);
 
         let mut gm = 0;
-        get_menu_choice("Please select a game mode:
+        if g == 1u32 {
+            get_menu_choice("Please select a game mode:
+1. vs Player
+2. vs Computer (Easy)
+3. vs Computer (Hard)
+4. vs Computer (Perfect)
+5. vs Computer (MCTS)
+6. vs Computer (Timed)", 6, &mut gm);
+        } else {
+            get_menu_choice("Please select a game mode:
 1. vs Player
 2. vs Computer (Easy)
-3. vs Computer (Hard)", 3, &mut gm);
+3. vs Computer (Hard)
+4. vs Computer (MCTS)
+5. vs Computer (Timed)", 5, &mut gm);
+        }
 
         // enter loop with a tree of user's choice
-        let result = match g {
+        let record = match g {
             1u32 => {
                 play_connect4(s, gm)
             },
             2u32 => {
-                let mut tok = 'N';
-                loop {
+                let player = loop {
                     println!("Please select a token to play as (T or O): ");
                     let mut s: String = "".to_string();
 
-                    match stdin().read_line(&mut s) {
-                        Err(_) => println!("Something went wrong reading input, please try again."),
-                        Ok(_) => {}
-                    };
+                    if stdin().read_line(&mut s).is_err() {
+                        println!("Something went wrong reading input, please try again.");
+                    }
 
-                    if s.trim().len() == 1 {
-                        tok = s.trim().chars().next().unwrap();
-                        if tok == 'T' || tok == 'O' {
-                            break;
-                        }
+                    match s.trim() {
+                        "T" => break Player::Two,
+                        "O" => break Player::One,
+                        _ => (),
                     }
                     println!("Please enter a valid token (T or O).");
-                }
+                };
 
-                play_otto(s, gm, tok)
+                play_otto(s, gm, player)
                 
             },
-            _ => "Something went wrong, please try again".to_string(),
+            _ => None,
         };
+        if let Some(record) = record {
+            save_record(&record);
+        }
         get_continue();
     }
 }
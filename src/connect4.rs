@@ -1,40 +1,200 @@
 use std::cmp;
-use rand::seq::SliceRandom;
 use std::io::{self, Write};
 use std::fmt;
 use super::board::*;
+use crate::budget::Budget;
+use crate::cli::{self, CliGame, Command, Computer};
+use crate::game::{self, Game, Outcome};
+use crate::mcts::{Mcts, Rollout, DEFAULT_ITERATIONS};
+use crate::notation;
+use crate::record::GameRecord;
+use crate::search::{Bot, BotConfig, SearchInfo, Searcher};
+use crate::solver;
+use crate::zobrist;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 
+/// One bit per cell, column-major, with `height + 1` bits per column. The
+/// extra bit on top of every column is never set, so shifting a bitboard
+/// horizontally or diagonally can't wrap a line from one column into the next.
+/// A `u128` is needed because the large 10x7 board takes 80 bits.
+pub type Bitboard = u128;
+
+/// Which rules a board is played by
 #[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Variant {
+    Classic,
+    /// Instead of dropping a disc, a player may pop one of their own discs
+    /// out of the bottom of a column. A full board isn't a draw, but the
+    /// same position coming up three times is.
+    PopOut,
+    /// Classic rules, but the outer columns start out full of alternating
+    /// discs. Played to five in a row on a 9x6 board, see five_in_a_row.
+    FiveInARow,
+    /// The board is filled a row at a time, then players pop their own discs
+    /// from the bottom. A disc that was part of a line is kept and its owner
+    /// goes again, any other goes back in at the top of another column. The
+    /// first to keep POP10_TARGET discs wins. We call it a draw if the side
+    /// to move has nothing to pop, or the same position comes up three times.
+    Pop10,
+}
+
+/// Discs a player has to keep to win Pop 10
+pub const POP10_TARGET: u32 = 10;
+
+/// A disc dropped into or popped out of a column, or placed on a cell of a
+/// board without gravity
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connect4Move {
+    pub col: u32,
+    /// the cell's row for MoveKind::Place, 0 is the top row
+    pub row: Option<u32>,
+    pub kind: MoveKind,
+}
+
+impl Connect4Move {
+    pub fn drop(col: u32) -> Connect4Move {
+        Connect4Move { col, row: None, kind: MoveKind::Drop }
+    }
+
+    pub fn pop(col: u32) -> Connect4Move {
+        Connect4Move { col, row: None, kind: MoveKind::Pop }
+    }
+
+    pub fn place(row: u32, col: u32) -> Connect4Move {
+        Connect4Move { col, row: Some(row), kind: MoveKind::Place }
+    }
+
+    /// Reads a move the way game records write it: a column, p and a column
+    /// for a pop, or (row,col) for a placement
+    pub fn parse(text: &str) -> Option<Connect4Move> {
+        if let Some(col) = text.strip_prefix('p') {
+            return col.parse().ok().map(Connect4Move::pop);
+        }
+        match text.strip_prefix('(').and_then(|cell| cell.strip_suffix(')')) {
+            Some(cell) => {
+                let (row, col) = cell.split_once(',')?;
+                Some(Connect4Move::place(row.parse().ok()?, col.parse().ok()?))
+            }
+            None => text.parse().ok().map(Connect4Move::drop),
+        }
+    }
+}
+
+/// Writes the move the way parse reads it
+impl fmt::Display for Connect4Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.kind, self.row) {
+            (MoveKind::Place, Some(row)) => write!(f, "({},{})", row, self.col),
+            (MoveKind::Pop, _) => write!(f, "p{}", self.col),
+            _ => write!(f, "{}", self.col),
+        }
+    }
+}
+
+impl From<&MoveRecord> for Connect4Move {
+    fn from(record: &MoveRecord) -> Connect4Move {
+        match record.kind {
+            MoveKind::Drop => Connect4Move::drop(record.col),
+            MoveKind::Pop => Connect4Move::pop(record.col),
+            MoveKind::Place => Connect4Move::place(record.row, record.col),
+        }
+    }
+}
+
+/// Serializes as a Connect4State, so loading a board plays its moves again
+/// and checks them rather than trusting the bitboards it's given
+#[wasm_bindgen]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "Connect4State", try_from = "Connect4State")]
 pub struct Connect4Board {
     width: u32,
     height: u32,
-    board: Vec<Vec<char>>,
+    // discs in a row needed to win
+    win_length: u32,
+    variant: Variant,
+    // discs[0] holds Player::One's X discs, discs[1] Player::Two's O discs
+    // and so on, only the first `players` are ever set
+    discs: [Bitboard; MAX_PLAYERS as usize],
+    // how many take turns, Player::One first
+    players: u32,
+    // what each player's discs look like on the terminal
+    symbols: [char; MAX_PLAYERS as usize],
+    // number of discs in each column
+    heights: Vec<u32>,
+    // every line of win_length cells on the board, for the AI's evaluation
+    windows: Vec<Bitboard>,
+    // Zobrist hash of the discs on the board
+    hash: u64,
+    // position_key() before every move in history, for spotting repetitions
+    keys: Vec<u64>,
+    // pops in history, Pop 10 is past filling the board once there's one
+    pops: u32,
+    // discs each player has kept in Pop 10
+    kept: [u32; MAX_PLAYERS as usize],
+    // the column a Pop 10 disc that wasn't kept came out of, until it goes
+    // back in
+    pending: Option<u32>,
+    // whether winning by the variant's rules loses instead
+    misere: bool,
+    // false when discs go on any empty cell instead of dropping
+    gravity: bool,
     last_row: Option<u32>,
     last_col: Option<u32>,
-    last_player: Option<char>,
+    last_player: Option<Player>,
+    // whose turn it is, X always goes first
+    side_to_move: Player,
+    // every move on the board, oldest first
+    history: Vec<MoveRecord>,
+    // moves taken back with undo, the next one to redo last
+    redone: Vec<MoveRecord>,
 }
 
 #[wasm_bindgen]
 impl Connect4Board {
     #[wasm_bindgen(constructor)]
     pub fn new(size: BoardSize) -> Connect4Board {
-        let (width, height) = match size {
-            BoardSize::Standard => (7, 6),
-            BoardSize::Large => (10, 7),
-        };
+        match size {
+            BoardSize::Standard => Connect4Board::sized(7, 6, 4, Variant::Classic),
+            BoardSize::Large => Connect4Board::sized(10, 7, 4, Variant::Classic),
+        }
+    }
 
-        Connect4Board {
-            width,
-            height,
-            board: vec![vec![' '; width as usize]; height as usize],
-            last_row: None,
-            last_col: None,
-            last_player: None,
+    /// An empty board `width` columns wide and `height` rows high, within
+    /// MIN_DIMENSION and MAX_WIDTH/MAX_HEIGHT
+    #[wasm_bindgen]
+    pub fn with_dimensions(width: u32, height: u32) -> Result<Connect4Board, DimensionError> {
+        Connect4Board::with_win_length(width, height, 4)
+    }
+
+    /// Like with_dimensions, but for Connect-N: `win_length` discs in a row
+    /// win instead of four. The line has to fit across or up the board.
+    #[wasm_bindgen]
+    pub fn with_win_length(width: u32, height: u32, win_length: u32) -> Result<Connect4Board, DimensionError> {
+        Connect4Board::with_variant(width, height, win_length, Variant::Classic)
+    }
+
+    /// Like with_win_length, but played by `variant`'s rules
+    #[wasm_bindgen]
+    pub fn with_variant(width: u32, height: u32, win_length: u32, variant: Variant) -> Result<Connect4Board, DimensionError> {
+        check_dimensions(width, height)?;
+        if win_length < MIN_WIN_LENGTH || win_length > cmp::max(width, height) {
+            return Err(DimensionError::WinLengthOutOfRange);
         }
+        Ok(Connect4Board::sized(width, height, win_length, variant))
     }
 
-    
+    /// The official Five-in-a-Row board: 9x6 with full outer columns
+    #[wasm_bindgen]
+    pub fn five_in_a_row() -> Connect4Board {
+        Connect4Board::sized(9, 6, 5, Variant::FiveInARow)
+    }
+
+
     #[wasm_bindgen]
     pub fn width(&self) -> u32 {
         self.width
@@ -45,6 +205,95 @@ impl Connect4Board {
         self.height
     }
 
+    /// Discs in a row needed to win
+    #[wasm_bindgen]
+    pub fn win_length(&self) -> u32 {
+        self.win_length
+    }
+
+    #[wasm_bindgen]
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// How many players take turns
+    #[wasm_bindgen]
+    pub fn players(&self) -> u32 {
+        self.players
+    }
+
+    /// Seats MIN_PLAYERS to MAX_PLAYERS players, who take turns from
+    /// Player::One on. PopOut and Pop 10 stay two-player, and misère is turned
+    /// off for more. Only before the first disc is down.
+    #[wasm_bindgen]
+    pub fn set_players(&mut self, players: u32) -> Result<(), MoveError> {
+        let two_player = matches!(self.variant, Variant::PopOut | Variant::Pop10);
+        if self.started() {
+            return Err(MoveError::GameAlreadyStarted);
+        }
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players) || (two_player && players != 2) {
+            return Err(MoveError::PlayersOutOfRange);
+        }
+        self.players = players;
+        self.misere &= players == 2;
+        Ok(())
+    }
+
+    /// The character `player`'s discs are shown as
+    #[wasm_bindgen]
+    pub fn symbol(&self, player: Player) -> char {
+        self.symbols[player as usize]
+    }
+
+    /// Shows `player`'s discs as `symbol` instead of their usual letter, see
+    /// set_symbols
+    #[wasm_bindgen]
+    pub fn set_symbol(&mut self, player: Player, symbol: char) -> Result<(), MoveError> {
+        let mut symbols = self.symbols;
+        symbols[player as usize] = symbol;
+        self.set_symbols(&symbols)
+    }
+
+    // whether any disc is down or any move has been played
+    fn started(&self) -> bool {
+        self.occupied() != 0 || !self.history.is_empty()
+    }
+
+    /// Whether the board is played misère
+    #[wasm_bindgen]
+    pub fn misere(&self) -> bool {
+        self.misere
+    }
+
+    /// Turns misère on or off: whoever would have won by the usual rules
+    /// loses instead. There's no telling who that would be with more than two
+    /// players, so those boards stay normal. Meant to be set before the first
+    /// move.
+    #[wasm_bindgen]
+    pub fn set_misere(&mut self, misere: bool) {
+        self.misere = misere && self.players == 2;
+    }
+
+    /// Whether discs drop to the bottom of their column
+    #[wasm_bindgen]
+    pub fn gravity(&self) -> bool {
+        self.gravity
+    }
+
+    /// Turns gravity off so discs go on any empty cell, Gomoku style, or back
+    /// on. Pops need gravity, so PopOut and Pop 10 boards always keep it.
+    /// Meant to be set before the first move.
+    #[wasm_bindgen]
+    pub fn set_gravity(&mut self, gravity: bool) {
+        self.gravity = gravity || matches!(self.variant, Variant::PopOut | Variant::Pop10);
+    }
+
+    /// Discs `player` has kept in Pop 10
+    #[wasm_bindgen]
+    pub fn kept(&self, player: Player) -> u32 {
+        self.kept[player as usize]
+    }
+
     #[wasm_bindgen]
     pub fn last_row(&self) -> Option<u32> {
         self.last_row
@@ -56,47 +305,513 @@ impl Connect4Board {
     }
 
     #[wasm_bindgen]
-    pub fn last_player(&self) -> Option<char> {
+    pub fn last_player(&self) -> Option<Player> {
         self.last_player
     }
 
+    /// The player whose disc goes in next
+    #[wasm_bindgen]
+    pub fn side_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    /// Drops a disc for the side to move into `col`, doing nothing if it's
+    /// full. Any moves taken back with undo can't be redone afterwards.
+    #[wasm_bindgen]
+    pub fn perform_move(&mut self, col: u32) {
+        if self.allows_move(col) {
+            self.redone.clear();
+            self.place(col);
+        }
+    }
+
+    /// Pops the side to move's disc out of the bottom of `col`, doing nothing
+    /// if that isn't allowed. Any moves taken back with undo can't be redone
+    /// afterwards.
+    #[wasm_bindgen]
+    pub fn perform_pop(&mut self, col: u32) {
+        if self.allows_pop(col) {
+            self.redone.clear();
+            self.pop(col);
+        }
+    }
+
+    /// Puts the side to move's disc on the empty cell at `row` (0 is the top
+    /// row) and `col` of a board without gravity, doing nothing if that isn't
+    /// allowed. Any moves taken back with undo can't be redone afterwards.
+    #[wasm_bindgen]
+    pub fn perform_place(&mut self, row: u32, col: u32) {
+        if self.allows_place(row, col) {
+            self.redone.clear();
+            self.place_at(row, col);
+        }
+    }
+
+    /// Takes back the last move, which redo can play again
+    #[wasm_bindgen]
+    pub fn undo(&mut self) -> Option<MoveRecord> {
+        let record = self.take_back()?;
+        self.redone.push(record);
+        Some(record)
+    }
+
+    /// Plays the last move taken back with undo again
+    #[wasm_bindgen]
+    pub fn redo(&mut self) -> Option<MoveRecord> {
+        let record = self.redone.pop()?;
+        match record.kind {
+            MoveKind::Drop => self.place(record.col),
+            MoveKind::Pop => self.pop(record.col),
+            MoveKind::Place => self.place_at(record.row, record.col),
+        }
+        Some(record)
+    }
+
+    /// Every move played so far, oldest first
+    #[wasm_bindgen]
+    pub fn history(&self) -> Vec<MoveRecord> {
+        self.history.clone()
+    }
+
+    /// The disc at `row` (0 is the top row) and `col`, empty off the board
+    #[wasm_bindgen]
+    pub fn get(&self, row: u32, col: u32) -> Cell {
+        self.owner(row, col).map_or(Cell::Empty, Player::disc)
+    }
+
+    /// Whose disc is at `row` (0 is the top row) and `col`, if anyone's.
+    /// None for cells off the board.
+    #[wasm_bindgen]
+    pub fn owner(&self, row: u32, col: u32) -> Option<Player> {
+        if row >= self.height || col >= self.width {
+            return None;
+        }
+        let bit = self.cell_bit(self.height - 1 - row, col);
+        Player::ALL.iter().copied().find(|&player| self.discs[player as usize] & bit != 0)
+    }
+
+    /// The position in the notation described in `notation`: the discs row
+    /// by row from the top, then whose turn it is. The rules aren't recorded.
+    #[wasm_bindgen]
+    pub fn to_notation(&self) -> String {
+        notation::write_position(self.width, self.height,
+            |row, col| self.owner(row, col).map(|player| player.disc().symbol()),
+            self.side_to_move.disc().symbol())
+    }
+
+    /// Reads a position written by to_notation, or the columns played from
+    /// the start of a standard game, one digit each with X first, like 3342.
+    /// Positions are played to four in a row by Classic rules, by as many
+    /// players as have discs down or the turn.
     #[wasm_bindgen]
-    pub fn perform_move(&mut self, col: u32, ox: char) {
-        let col = col as usize;
-        for row in (0..self.height as usize).rev() {
-            if self.board[row][col] == ' ' {
-                self.board[row][col] = ox;
-                self.last_row = Some(row as u32);
-                self.last_col = Some(col as u32);
-                self.last_player = Some(ox);
-                break;
+    pub fn from_notation(notation: &str) -> Result<Connect4Board, NotationError> {
+        let notation = notation.trim();
+        if !notation::is_position(notation) {
+            let mut board = Connect4Board::new(BoardSize::Standard);
+            for (number, c) in (1..).zip(notation.chars()) {
+                let col = c.to_digit(10).ok_or(NotationError::Unreadable)?;
+                board.try_play(col, board.side_to_move).map_err(|error| NotationError::IllegalMove(number, error))?;
             }
+            return Ok(board);
+        }
+
+        Connect4Board::read_position(notation, true)
+    }
+
+    /// The board as a JavaScript object, see Connect4State
+    #[wasm_bindgen]
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(self)?)
+    }
+
+    /// Reads a board written by to_js
+    #[wasm_bindgen]
+    pub fn from_js(value: JsValue) -> Result<Connect4Board, JsValue> {
+        Ok(serde_wasm_bindgen::from_value(value)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn status(&self) -> GameStatus {
+        self.outcome().into()
+    }
+
+    /// The player who connected four, if anyone has
+    #[wasm_bindgen]
+    pub fn winner(&self) -> Option<Player> {
+        match self.outcome() {
+            Outcome::Win(player) => Some(player),
+            _ => None,
         }
     }
 
+    /// Drops a disc like perform_move, but checks the move first and leaves
+    /// the board alone if it's illegal or it isn't `player`'s turn
+    #[wasm_bindgen]
+    pub fn try_play(&mut self, col: u32, player: Player) -> Result<MoveOutcome, MoveError> {
+        self.check_move(col, player)?;
+        self.perform_move(col);
+        Ok(MoveOutcome { row: self.height - self.heights[col as usize], col, status: self.status() })
+    }
+
+    /// Pops a disc like perform_pop, but checks the move first and leaves the
+    /// board alone if it's illegal or it isn't `player`'s turn
+    #[wasm_bindgen]
+    pub fn try_pop(&mut self, col: u32, player: Player) -> Result<MoveOutcome, MoveError> {
+        self.check_pop(col, player)?;
+        self.perform_pop(col);
+        Ok(MoveOutcome { row: self.height - 1, col, status: self.status() })
+    }
+
+    /// Places a disc like perform_place, but checks the move first and leaves
+    /// the board alone if it's illegal or it isn't `player`'s turn
+    #[wasm_bindgen]
+    pub fn try_place(&mut self, row: u32, col: u32, player: Player) -> Result<MoveOutcome, MoveError> {
+        self.check_place(row, col, player)?;
+        self.perform_place(row, col);
+        Ok(MoveOutcome { row, col, status: self.status() })
+    }
+
     #[wasm_bindgen]
     pub fn is_terminal(&self) -> bool {
-        self.is_draw() || self.has_winner()
+        self.outcome() != Outcome::InProgress
     }
 
+    /// A full board in Classic and Five-in-a-Row. In PopOut and Pop 10 the
+    /// side to move must have nothing to play, or the position has come up
+    /// for the third time.
     #[wasm_bindgen]
     pub fn is_draw(&self) -> bool {
-        self.available_moves().is_empty()
+        match self.variant {
+            Variant::Classic | Variant::FiveInARow => self.is_full(),
+            Variant::PopOut | Variant::Pop10 => {
+                let key = self.position_key();
+                let stuck = (0..self.width).all(|col| !self.allows_move(col) && !self.allows_pop(col));
+                stuck || self.keys.iter().filter(|&&earlier| earlier == key).count() >= 2
+            }
+        }
+    }
+
+    /// Whether the side to move could put a disc on the cell at `row` (0 is
+    /// the top row) and `col`
+    #[wasm_bindgen]
+    pub fn allows_place(&self, row: u32, col: u32) -> bool {
+        !self.gravity && row < self.height && col < self.width && self.get(row, col) == Cell::Empty
+    }
+
+    /// Whether the side to move could pop their disc out of `col`
+    #[wasm_bindgen]
+    pub fn allows_pop(&self, col: u32) -> bool {
+        let allowed = match self.variant {
+            Variant::PopOut => true,
+            Variant::Pop10 => self.popping() && self.pending.is_none(),
+            Variant::Classic | Variant::FiveInARow => false,
+        };
+        allowed && col < self.width && self.discs[self.side_to_move as usize] & self.cell_bit(0, col) != 0
     }
 }
 
 impl Connect4Board {
+    // reads a position written by to_notation, where discs may float if
+    // `grounded` is false
+    fn read_position(notation: &str, grounded: bool) -> Result<Connect4Board, NotationError> {
+        let (rows, side) = notation::read_position(notation)?;
+        let seat = |symbol| Player::ALL.iter().copied().find(|player| player.disc().symbol() == symbol);
+        let side = seat(side).ok_or(NotationError::Unreadable)?;
+        let mut board = Connect4Board::with_dimensions(rows[0].len() as u32, rows.len() as u32)?;
+        if grounded {
+            notation::check_grounded(&rows)?;
+        }
+
+        let mut counts = [0; MAX_PLAYERS as usize];
+        let mut cells = Vec::new();
+        for row in &rows {
+            let mut line = Vec::new();
+            for &piece in row {
+                match piece.map(|symbol| seat(symbol).ok_or(NotationError::Unreadable)).transpose()? {
+                    Some(player) => {
+                        counts[player as usize] += 1;
+                        line.push(player.disc());
+                    }
+                    None => line.push(Cell::Empty),
+                }
+            }
+            cells.push(line);
+        }
+
+        let seated = Player::ALL.iter().rposition(|&player| counts[player as usize] > 0 || player == side);
+        let players = cmp::max(MIN_PLAYERS, seated.unwrap() as u32 + 1);
+        board.set_players(players).map_err(|_| DimensionError::PlayersOutOfRange)?;
+        // X goes first and the turn goes round, so every player has as many
+        // discs as the one before or one fewer
+        let counts = &counts[..players as usize];
+        if counts.windows(2).any(|pair| pair[1] > pair[0]) || counts[0] - counts[counts.len() - 1] > 1 {
+            return Err(NotationError::ImpossibleCounts);
+        }
+        board.set_board(cells);
+        if board.side_to_move != side {
+            return Err(NotationError::WrongSideToMove);
+        }
+        Ok(board)
+    }
+
+    fn sized(width: u32, height: u32, win_length: u32, variant: Variant) -> Connect4Board {
+        let mut board = Connect4Board {
+            width,
+            height,
+            win_length,
+            variant,
+            discs: [0; MAX_PLAYERS as usize],
+            players: 2,
+            symbols: Player::ALL.map(|player| player.disc().symbol()),
+            heights: vec![0; width as usize],
+            windows: Vec::new(),
+            hash: 0,
+            keys: Vec::new(),
+            pops: 0,
+            kept: [0; MAX_PLAYERS as usize],
+            pending: None,
+            misere: false,
+            gravity: true,
+            last_row: None,
+            last_col: None,
+            last_player: None,
+            side_to_move: Player::One,
+            history: Vec::new(),
+            redone: Vec::new(),
+        };
+        board.windows = board.line_windows();
+        if variant == Variant::FiveInARow {
+            board.fill_outer_columns();
+        }
+        board
+    }
+
+    // fills the outer columns with alternating discs, X at the bottom on the
+    // left and O on the right
+    fn fill_outer_columns(&mut self) {
+        for row in 0..self.height {
+            let left = if row % 2 == 0 { Player::One } else { Player::Two };
+            for (col, player) in [(0, left), (self.width - 1, left.other())] {
+                self.discs[player as usize] |= self.cell_bit(row, col);
+                self.hash ^= zobrist::piece_key(self.cell_index(row, col), player as usize);
+            }
+        }
+        self.heights[0] = self.height;
+        self.heights[self.width as usize - 1] = self.height;
+    }
+
+    /// Every move the side to move may make: drops and pops with gravity,
+    /// placements on every empty cell without
+    pub fn available_moves(&self) -> Vec<Connect4Move> {
+        if !self.gravity {
+            return (0..self.height)
+                .flat_map(|row| (0..self.width).map(move |col| Connect4Move::place(row, col)))
+                .filter(|mv| self.allows_place(mv.row.unwrap(), mv.col))
+                .collect();
+        }
+        let drops = (0..self.width).filter(|&col| self.allows_move(col)).map(Connect4Move::drop);
+        let pops = (0..self.width).filter(|&col| self.allows_pop(col)).map(Connect4Move::pop);
+        drops.chain(pops).collect()
+    }
 
-    pub fn available_moves(&self) -> Vec<u32> {
-        (0..self.width).filter(|&col| self.allows_move(col)).collect()
+    /// Plays `mv` for `player` like try_play, try_pop or try_place would
+    pub fn try_move(&mut self, mv: Connect4Move, player: Player) -> Result<MoveOutcome, MoveError> {
+        match (mv.kind, mv.row) {
+            (MoveKind::Drop, _) => self.try_play(mv.col, player),
+            (MoveKind::Pop, _) => self.try_pop(mv.col, player),
+            (MoveKind::Place, Some(row)) => self.try_place(row, mv.col, player),
+            (MoveKind::Place, None) => Err(MoveError::CellOutOfRange),
+        }
+    }
+
+    /// Rebuilds a recorded game: the rules and start position come from the
+    /// record's tags, then every move is checked as it's played again
+    pub fn from_record(record: &GameRecord) -> Result<Connect4Board, NotationError> {
+        let rules: Vec<&str> = record.tag("Rules").unwrap_or("Classic").split_whitespace().collect();
+        let variant = match rules.first() {
+            None | Some(&"Classic") => Variant::Classic,
+            Some(&"PopOut") => Variant::PopOut,
+            Some(&"FiveInARow") => Variant::FiveInARow,
+            Some(&"Pop10") => Variant::Pop10,
+            Some(_) => return Err(NotationError::Unreadable),
+        };
+        let win_length = match record.tag("WinLength") {
+            Some(length) => length.parse().map_err(|_| NotationError::Unreadable)?,
+            None => 4,
+        };
+
+        let players = rules.iter().find_map(|rule| rule.strip_suffix("-player")).map(str::parse);
+        let players = match players {
+            Some(players) => players.map_err(|_| NotationError::Unreadable)?,
+            None => MIN_PLAYERS,
+        };
+        let history = record.moves().iter()
+            .map(|text| Connect4Move::parse(text).ok_or(NotationError::Unreadable))
+            .collect::<Result<_, _>>()?;
+
+        Connect4Board::try_from(Connect4State {
+            win_length,
+            variant,
+            players,
+            symbols: Player::ALL.map(|player| player.disc().symbol()),
+            misere: rules.contains(&"misere"),
+            gravity: !rules.contains(&"no-gravity"),
+            start: record.tag("Start").unwrap_or("").to_string(),
+            history,
+            redone: Vec::new(),
+        })
+    }
+
+    /// The board as JSON, see Connect4State
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a board always serializes")
+    }
+
+    /// Reads a board written by to_json
+    pub fn from_json(json: &str) -> serde_json::Result<Connect4Board> {
+        serde_json::from_str(json)
+    }
+
+    /// Shows the discs of the players from Player::One on as `symbols`, the
+    /// rest keeping theirs. No two players can share a symbol, none can be a
+    /// space, a digit or `/`, and they can only be changed before the first
+    /// disc is down.
+    pub fn set_symbols(&mut self, symbols: &[char]) -> Result<(), MoveError> {
+        if self.started() {
+            return Err(MoveError::GameAlreadyStarted);
+        }
+        if symbols.len() > MAX_PLAYERS as usize {
+            return Err(MoveError::InvalidSymbol);
+        }
+        let mut all = self.symbols;
+        all[..symbols.len()].copy_from_slice(symbols);
+        let shared = all.iter().enumerate().any(|(i, symbol)| all[..i].contains(symbol));
+        let unreadable = all.iter().any(|&symbol| symbol.is_whitespace() || symbol.is_control() || symbol.is_ascii_digit() || symbol == '/');
+        if shared || unreadable {
+            return Err(MoveError::InvalidSymbol);
+        }
+        self.symbols = all;
+        Ok(())
+    }
+
+    /// Returns why `player` can't drop a disc into `col` right now, if they can't
+    pub fn check_move(&self, col: u32, player: Player) -> Result<(), MoveError> {
+        if self.is_terminal() {
+            Err(MoveError::GameAlreadyOver)
+        } else if player != self.side_to_move {
+            Err(MoveError::WrongPlayerToMove)
+        } else if col >= self.width {
+            Err(MoveError::ColumnOutOfRange)
+        } else if !self.gravity {
+            Err(MoveError::DropNotAllowed)
+        } else if self.heights[col as usize] == self.height {
+            Err(MoveError::ColumnFull)
+        } else if !self.allows_move(col) {
+            // only Pop 10 turns drops down in columns with room
+            Err(if self.pending.is_some() {
+                MoveError::PickAnotherColumn
+            } else if self.popping() {
+                MoveError::MustPop
+            } else {
+                MoveError::FillLowestRowFirst
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns why `player` can't pop their disc out of `col` right now, if they can't
+    pub fn check_pop(&self, col: u32, player: Player) -> Result<(), MoveError> {
+        if self.is_terminal() {
+            Err(MoveError::GameAlreadyOver)
+        } else if player != self.side_to_move {
+            Err(MoveError::WrongPlayerToMove)
+        } else if col >= self.width {
+            Err(MoveError::ColumnOutOfRange)
+        } else if !self.allows_pop(col) {
+            let own = self.discs[player as usize] & self.cell_bit(0, col) != 0;
+            Err(if own { MoveError::PopNotAllowed } else { MoveError::NotYourDisc })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns why `player` can't put a disc on the cell at `row` and `col`
+    /// right now, if they can't
+    pub fn check_place(&self, row: u32, col: u32, player: Player) -> Result<(), MoveError> {
+        if self.is_terminal() {
+            Err(MoveError::GameAlreadyOver)
+        } else if player != self.side_to_move {
+            Err(MoveError::WrongPlayerToMove)
+        } else if self.gravity {
+            Err(MoveError::PlacementNotAllowed)
+        } else if row >= self.height || col >= self.width {
+            Err(MoveError::CellOutOfRange)
+        } else if !self.allows_place(row, col) {
+            Err(MoveError::CellTaken)
+        } else {
+            Ok(())
+        }
     }
 
     pub fn allows_move(&self, col: u32) -> bool {
-        col < self.width && self.board[0][col as usize] == ' '
+        if !self.gravity || col >= self.width || self.heights[col as usize] == self.height {
+            return false;
+        }
+        match self.variant {
+            Variant::Pop10 => match self.pending {
+                // back in where it came from only if there's nowhere else
+                Some(popped) => col != popped || (0..self.width).all(|other| {
+                    other == popped || self.heights[other as usize] == self.height
+                }),
+                None if self.popping() => false,
+                None => self.heights[col as usize] == *self.heights.iter().min().unwrap(),
+            },
+            _ => true,
+        }
     }
 
-    pub fn set_board(&mut self, new_board: Vec<Vec<char>>) {
-        self.board = new_board;
+    fn is_full(&self) -> bool {
+        self.heights.iter().all(|&filled| filled == self.height)
+    }
+
+    // whether a Pop 10 game is past filling the board
+    fn popping(&self) -> bool {
+        self.pops > 0 || self.is_full()
+    }
+
+    /// Sets up a position directly. The history is cleared since there's no
+    /// knowing how the position came about, and it's X's turn unless X has
+    /// more discs down, in which case it's the first player with fewer.
+    pub fn set_board(&mut self, new_board: Vec<Vec<Cell>>) {
+        self.history.clear();
+        self.redone.clear();
+        self.keys.clear();
+        self.pops = 0;
+        self.pending = None;
+        self.discs = [0; MAX_PLAYERS as usize];
+        self.heights = vec![0; self.width as usize];
+        self.hash = 0;
+        let seats = &Player::ALL[..self.players as usize];
+        for (row, cells) in new_board.iter().enumerate() {
+            let from_bottom = self.height - 1 - row as u32;
+            for (col, &cell) in cells.iter().enumerate() {
+                let player = match seats.iter().find(|player| player.disc() == cell) {
+                    Some(&player) => player,
+                    None => continue,
+                };
+                let col = col as u32;
+                self.discs[player as usize] |= self.cell_bit(from_bottom, col);
+                self.hash ^= zobrist::piece_key(self.cell_index(from_bottom, col), player as usize);
+                let filled = &mut self.heights[col as usize];
+                // without gravity a column's discs needn't sit on each other
+                *filled = if self.gravity { cmp::max(*filled, from_bottom + 1) } else { *filled + 1 };
+            }
+        }
+        let x = self.discs[0].count_ones();
+        self.side_to_move = seats.iter().copied()
+            .find(|&player| self.discs[player as usize].count_ones() < x)
+            .unwrap_or(Player::One);
     }
 
     pub fn set_last_col(&mut self, col: Option<u32>) {
@@ -107,309 +822,941 @@ impl Connect4Board {
         self.last_row = row;
     }
 
-    pub fn set_last_player(&mut self, player: Option<char>) {
+    pub fn set_last_player(&mut self, player: Option<Player>) {
         self.last_player = player;
     }
 
-    pub fn undo_move(&mut self, col: u32) {
-        let col = col as usize;
-        for row in 0..self.height as usize {
-            if self.board[row][col] != ' ' {
-                self.board[row][col] = ' ';
-                break;
+    // drops the side to move's disc, records it and passes the turn on. The
+    // search plays its moves through here so it doesn't forget what undo took back
+    fn place(&mut self, col: u32) {
+        let player = self.side_to_move;
+        let filled = self.heights[col as usize];
+        self.keys.push(self.position_key());
+        self.history.push(MoveRecord {
+            col,
+            row: self.height - 1 - filled,
+            token: None,
+            kind: MoveKind::Drop,
+            player,
+            prior_row: self.last_row,
+            prior_col: self.last_col,
+            prior_player: self.last_player,
+        });
+        self.discs[player as usize] |= self.cell_bit(filled, col);
+        self.hash ^= zobrist::piece_key(self.cell_index(filled, col), player as usize);
+        self.heights[col as usize] += 1;
+        self.last_row = Some(self.height - 1 - filled);
+        self.last_col = Some(col);
+        self.last_player = Some(player);
+        self.side_to_move = player.next(self.players);
+        self.pending = None;
+    }
+
+    // puts the side to move's disc on the cell at `row` (from the top) and
+    // `col` of a board without gravity, records it and passes the turn on
+    fn place_at(&mut self, row: u32, col: u32) {
+        let player = self.side_to_move;
+        let from_bottom = self.height - 1 - row;
+        self.keys.push(self.position_key());
+        self.history.push(MoveRecord {
+            col,
+            row,
+            token: None,
+            kind: MoveKind::Place,
+            player,
+            prior_row: self.last_row,
+            prior_col: self.last_col,
+            prior_player: self.last_player,
+        });
+        self.discs[player as usize] |= self.cell_bit(from_bottom, col);
+        self.hash ^= zobrist::piece_key(self.cell_index(from_bottom, col), player as usize);
+        self.heights[col as usize] += 1;
+        self.last_row = Some(row);
+        self.last_col = Some(col);
+        self.last_player = Some(player);
+        self.side_to_move = player.next(self.players);
+    }
+
+    // pops the side to move's disc out of the bottom of `col`, records it and
+    // passes the turn on. In Pop 10 the turn stays, the popped disc is either
+    // kept or pending until it goes back in.
+    fn pop(&mut self, col: u32) {
+        let player = self.side_to_move;
+        self.keys.push(self.position_key());
+        self.history.push(MoveRecord {
+            col,
+            row: self.height - 1,
+            token: None,
+            kind: MoveKind::Pop,
+            player,
+            prior_row: self.last_row,
+            prior_col: self.last_col,
+            prior_player: self.last_player,
+        });
+        let in_line = self.in_line(self.discs[player as usize], self.cell_bit(0, col));
+        self.hash ^= self.column_hash(col);
+        let mask = self.column_mask(col);
+        for discs in self.discs.iter_mut() {
+            // the popped disc falls into the padding bit below and is masked off
+            *discs = (*discs & !mask) | ((*discs & mask) >> 1 & mask);
+        }
+        self.heights[col as usize] -= 1;
+        self.hash ^= self.column_hash(col);
+        self.pops += 1;
+        self.last_row = Some(self.height - 1);
+        self.last_col = Some(col);
+        self.last_player = Some(player);
+        match self.variant {
+            Variant::Pop10 if in_line => self.kept[player as usize] += 1,
+            Variant::Pop10 => self.pending = Some(col),
+            _ => self.side_to_move = player.next(self.players),
+        }
+    }
+
+    // removes the last disc dropped, or puts back the last disc popped, and
+    // restores the last_* fields from before it
+    fn take_back(&mut self) -> Option<MoveRecord> {
+        let record = self.history.pop()?;
+        self.keys.pop();
+        let col = record.col;
+        match record.kind {
+            MoveKind::Drop => {
+                self.heights[col as usize] -= 1;
+                let row = self.heights[col as usize];
+                let bit = self.cell_bit(row, col);
+                self.hash ^= zobrist::piece_key(self.cell_index(row, col), record.player as usize);
+                self.discs[record.player as usize] &= !bit;
+                // a Pop 10 drop straight after a pop put the popped disc back in
+                if let Some(popped) = self.history.last().filter(|before| before.kind == MoveKind::Pop) {
+                    if self.variant == Variant::Pop10 {
+                        self.pending = Some(popped.col);
+                    }
+                }
+            }
+            MoveKind::Pop => {
+                if self.variant == Variant::Pop10 {
+                    match self.pending.take() {
+                        Some(_) => (),
+                        None => self.kept[record.player as usize] -= 1,
+                    }
+                }
+                self.pops -= 1;
+                self.hash ^= self.column_hash(col);
+                let mask = self.column_mask(col);
+                for discs in self.discs.iter_mut() {
+                    *discs = (*discs & !mask) | ((*discs & mask) << 1 & mask);
+                }
+                self.discs[record.player as usize] |= self.cell_bit(0, col);
+                self.heights[col as usize] += 1;
+                self.hash ^= self.column_hash(col);
+            }
+            MoveKind::Place => {
+                let from_bottom = self.height - 1 - record.row;
+                self.hash ^= zobrist::piece_key(self.cell_index(from_bottom, col), record.player as usize);
+                self.discs[record.player as usize] &= !self.cell_bit(from_bottom, col);
+                self.heights[col as usize] -= 1;
             }
         }
+        self.last_row = record.prior_row;
+        self.last_col = record.prior_col;
+        self.last_player = record.prior_player;
+        self.side_to_move = record.player;
+        Some(record)
     }
 
+    /// Whether any player has a line. Only a pop can give both players one
+    /// at once, see `outcome`. In Pop 10 lines don't win, kept discs do.
     pub fn has_winner(&self) -> bool {
-        let row = self.last_row;
-        let col = self.last_col;
-        let ox = self.last_player;
+        match self.variant {
+            Variant::Pop10 => self.kept.iter().any(|&kept| kept >= POP10_TARGET),
+            _ => self.discs.iter().any(|&discs| self.connects(discs)),
+        }
+    }
 
-        if row.is_none() || col.is_none() || ox.is_none() {
-            return false;
+    // whether the disc at `bit` is in a line of win_length of `discs`
+    fn in_line(&self, discs: Bitboard, bit: Bitboard) -> bool {
+        let column = self.height + 1;
+        [1, column - 1, column, column + 1].iter().any(|&shift| {
+            let mut length = 1;
+            let (mut down, mut up) = (bit >> shift, bit << shift);
+            while discs & down != 0 {
+                length += 1;
+                down >>= shift;
+            }
+            while discs & up != 0 {
+                length += 1;
+                up <<= shift;
+            }
+            length >= self.win_length
+        })
+    }
+
+    // Zobrist hash of the discs in `col`, which must be stacked from the bottom
+    fn column_hash(&self, col: u32) -> u64 {
+        (0..self.heights[col as usize]).fold(0, |hash, row| {
+            let player = if self.discs[0] & self.cell_bit(row, col) != 0 { 0 } else { 1 };
+            hash ^ zobrist::piece_key(self.cell_index(row, col), player)
+        })
+    }
+
+    // Zobrist hash of the discs and Pop 10's kept and pending discs
+    fn state_hash(&self) -> u64 {
+        let kept = (0..self.players as usize).fold(self.hash, |hash, player| hash ^ zobrist::kept_key(player, self.kept[player]));
+        match self.pending {
+            Some(col) => kept ^ zobrist::pending_key(col),
+            None => kept,
         }
+    }
 
-        let row = row.unwrap() as usize;
-        let col = col.unwrap() as usize;
-        let ox = ox.unwrap();
+    // the discs and whose turn it is, the same position twice gets the same
+    // key. Only the two-player PopOut and Pop 10 look for repetitions.
+    fn position_key(&self) -> u64 {
+        self.state_hash() ^ zobrist::side_key(self.side_to_move as usize)
+    }
 
-        for c in cmp::max(0, col as i32 - 3) as usize..cmp::min(self.width as usize - 3, col + 1) {
-            if [self.board[row][c], self.board[row][c + 1], self.board[row][c + 2], self.board[row][c + 3]].iter().all(|&x| x == ox) {
-                return true;
+    /// Bitboard of every disc belonging to `player`
+    pub fn discs(&self, player: Player) -> Bitboard {
+        self.discs[player as usize]
+    }
+
+    /// Bitboard of every disc on the board, whoever's it is
+    pub fn occupied(&self) -> Bitboard {
+        self.discs.iter().fold(0, |all, &discs| all | discs)
+    }
+
+    /// Bitboard with every playable cell of column `col` set
+    pub fn column_mask(&self, col: u32) -> Bitboard {
+        ((1 << self.height) - 1) << (col * (self.height + 1))
+    }
+
+    /// Bitboard of the middle column, or the middle two on an even width
+    pub fn center_mask(&self) -> Bitboard {
+        game::center_columns(self.width).fold(0, |mask, col| mask | self.column_mask(col))
+    }
+
+    /// Bitboards of every horizontal, vertical and diagonal line of win_length cells
+    pub fn windows(&self) -> &[Bitboard] {
+        &self.windows
+    }
+
+    fn line_windows(&self) -> Vec<Bitboard> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let last = self.win_length as i32 - 1;
+        let mut windows = Vec::new();
+        for (dc, dr) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for col in 0..width {
+                for row in 0..height {
+                    let (end_col, end_row) = (col + last * dc, row + last * dr);
+                    if end_col >= width || end_row < 0 || end_row >= height {
+                        continue;
+                    }
+                    let window = (0..=last).fold(0, |window, i| {
+                        window | self.cell_bit((row + i * dr) as u32, (col + i * dc) as u32)
+                    });
+                    windows.push(window);
+                }
             }
         }
+        windows
+    }
 
-        if row < self.height as usize - 3 {
-            if [self.board[row][col], self.board[row + 1][col], self.board[row + 2][col], self.board[row + 3][col]].iter().all(|&x| x == ox) {
+    // `row` counts up from the bottom of the board here
+    fn cell_index(&self, row: u32, col: u32) -> usize {
+        (col * (self.height + 1) + row) as usize
+    }
+
+    fn cell_bit(&self, row: u32, col: u32) -> Bitboard {
+        1 << self.cell_index(row, col)
+    }
+
+    // whether `discs` has win_length in a line
+    fn connects(&self, discs: Bitboard) -> bool {
+        let column = self.height + 1;
+        // vertical, anti-diagonal, horizontal and diagonal neighbours
+        for shift in [1, column - 1, column, column + 1] {
+            // bits still set start a run of `run` discs
+            let (mut starts, mut run) = (discs, 1);
+            while run < self.win_length {
+                let step = cmp::min(run, self.win_length - run);
+                starts &= starts >> (step * shift);
+                run += step;
+            }
+            if starts != 0 {
                 return true;
             }
         }
+        false
+    }
 
-        for i in 0..4 {
-            let r = row as i32 - i as i32;
-            let c = col as i32 - i as i32;
-            if r >= 0 && r < (self.height - 3) as i32 && c >= 0 && c < (self.width - 3) as i32 {
-                let r = r as usize;
-                let c = c as usize;
-                if [self.board[r][c], self.board[r + 1][c + 1], self.board[r + 2][c + 2], self.board[r + 3][c + 3]].iter().all(|&x| x == ox) {
-                    return true;
-                }
-            }
+    pub fn host_game(&mut self) -> GameRecord {
+        println!("Welcome to Connect Four!\n");
+        cli::host_game(self, Vec::new())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn host_game_AI(&mut self, difficulty: Difficulty, people: u32, think_millis: Option<u32>) -> GameRecord {
+        println!("Welcome to Connect Four vs AI mode!\n");
+        // the people take the first `people` seats, and each seat left gets
+        // an AI of its own
+        let seats = &Player::ALL[people as usize..self.players as usize];
+        let mut ais: Vec<Connect4AI> = seats.iter().map(|_| Connect4AI::new(difficulty)).collect();
+        let computers = seats.iter().zip(ais.iter_mut())
+            .map(|(&plays, ai)| Computer { plays, bot: ai as &mut dyn Bot<Connect4Board>, think_millis })
+            .collect();
+        cli::host_game(self, computers)
+    }
+
+}
+
+/// A board the way serde writes it: the rules, the position before the first
+/// move in notation, and the moves since. The bitboards, hash and Pop 10
+/// counts all follow from those, so they're worked out again by playing the
+/// moves when a board is loaded, and a move that isn't legal fails the load.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connect4State {
+    pub win_length: u32,
+    pub variant: Variant,
+    /// the start position only shows the players with discs down or the turn
+    pub players: u32,
+    pub symbols: [char; MAX_PLAYERS as usize],
+    pub misere: bool,
+    pub gravity: bool,
+    pub start: String,
+    pub history: Vec<Connect4Move>,
+    /// moves taken back with undo, the next one to redo last
+    pub redone: Vec<Connect4Move>,
+}
+
+impl From<Connect4Board> for Connect4State {
+    fn from(mut board: Connect4Board) -> Connect4State {
+        let history = board.history.iter().map(Connect4Move::from).collect();
+        let redone = board.redone.iter().map(Connect4Move::from).collect();
+        while board.undo().is_some() {}
+        Connect4State {
+            win_length: board.win_length,
+            variant: board.variant,
+            players: board.players,
+            symbols: board.symbols,
+            misere: board.misere,
+            gravity: board.gravity,
+            start: board.to_notation(),
+            history,
+            redone,
         }
+    }
+}
 
-        for i in 0..4 {
-            let r = row as i32 - i as i32;
-            let c = col as i32 + i as i32;
-            if r >= 0 && r < (self.height - 3) as i32 && c >= 3 && c < self.width as i32 {
-                let r = r as usize;
-                let c = c as usize;
-                if [self.board[r][c], self.board[r + 1][c - 1], self.board[r + 2][c - 2], self.board[r + 3][c - 3]].iter().all(|&x| x == ox) {
-                    return true;
-                }
-            }
+impl TryFrom<Connect4State> for Connect4Board {
+    type Error = NotationError;
+
+    fn try_from(state: Connect4State) -> Result<Connect4Board, NotationError> {
+        let start = Connect4Board::read_position(&state.start, state.gravity)?;
+        let mut board = Connect4Board::with_variant(start.width, start.height, state.win_length, state.variant)?;
+        board.set_players(cmp::max(state.players, start.players)).map_err(|_| DimensionError::PlayersOutOfRange)?;
+        board.set_symbols(&state.symbols).map_err(|_| NotationError::Unreadable)?;
+        board.set_gravity(state.gravity);
+        board.set_misere(state.misere);
+        board.set_board((0..start.height).map(|row| (0..start.width).map(|col| start.get(row, col)).collect()).collect());
+        // moves that were taken back get played and checked too, then taken
+        // back again
+        let moves = state.history.iter().chain(state.redone.iter().rev());
+        for (number, &mv) in (1..).zip(moves) {
+            board.try_move(mv, board.side_to_move).map_err(|error| NotationError::IllegalMove(number, error))?;
+        }
+        for _ in &state.redone {
+            board.undo();
         }
+        Ok(board)
+    }
+}
 
-        false
+impl Game for Connect4Board {
+    type Move = Connect4Move;
+    type Player = Player;
+
+    fn side_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    fn legal_moves(&self) -> Vec<Connect4Move> {
+        self.available_moves()
+    }
+
+    fn play(&mut self, mv: Connect4Move) {
+        match (mv.kind, mv.row) {
+            (MoveKind::Drop, _) => self.place(mv.col),
+            (MoveKind::Pop, _) => self.pop(mv.col),
+            (MoveKind::Place, Some(row)) => self.place_at(row, mv.col),
+            (MoveKind::Place, None) => panic!("a placement needs a row"),
+        }
+    }
+
+    fn undo(&mut self, _mv: Connect4Move) {
+        self.take_back();
+    }
+
+    fn outcome(&self) -> GameOutcome {
+        match self.usual_outcome() {
+            Outcome::Win(player) if self.misere => Outcome::Win(player.other()),
+            outcome => outcome,
+        }
+    }
+
+    /// Zobrist hash of the discs on the board and, in Pop 10, the discs kept
+    /// or waiting to go back in, see `zobrist`
+    fn zobrist_key(&self) -> u64 {
+        self.state_hash()
     }
 
-    pub fn game_value(&self) -> i32 {
-        if self.has_winner() {
-            match self.last_player {
-                Some('X') => i32::MAX,
-                Some('O') => i32::MIN,
-                _ => 0,
+    fn side_key(&self) -> u64 {
+        zobrist::side_key(self.side_to_move as usize)
+    }
+}
+
+impl Connect4Board {
+    /// The outcome if the board weren't misère. A pop that completes lines
+    /// for both players wins for the player who popped, and one that only
+    /// completes the opponent's loses.
+    fn usual_outcome(&self) -> GameOutcome {
+        if self.variant == Variant::Pop10 {
+            return match self.kept.iter().position(|&kept| kept >= POP10_TARGET) {
+                Some(0) => Outcome::Win(Player::One),
+                Some(_) => Outcome::Win(Player::Two),
+                None if self.is_draw() => Outcome::Draw,
+                None => Outcome::InProgress,
+            };
+        }
+        if let Some(last) = self.last_player {
+            // the mover's line counts first, then the others' in turn order
+            let mut player = last;
+            for _ in 0..self.players {
+                if self.connects(self.discs(player)) {
+                    return Outcome::Win(player);
+                }
+                player = player.next(self.players);
             }
-        } else {
-            0
         }
+        if self.is_draw() { Outcome::Draw } else { Outcome::InProgress }
     }
+}
 
-    pub fn get_player_move(&mut self, ox: char) -> u32 {
-        let ai = Connect4AI::new(Difficulty::Hard);
+impl CliGame for Connect4Board {
+    fn read_move(&self) -> Command<Connect4Move> {
+        let player = self.side_to_move;
+        let symbol = self.symbol(player);
         loop {
-            print!("{}'s choice: ", ox);
+            match self.variant {
+                _ if !self.gravity => print!("{}'s choice (row and column, e.g. 2 3): ", symbol),
+                Variant::PopOut | Variant::Pop10 => print!("{}'s choice (p before a column pops): ", symbol),
+                Variant::Classic | Variant::FiveInARow => print!("{}'s choice: ", symbol),
+            }
             io::stdout().flush().unwrap();
             let mut input = String::new();
             io::stdin().read_line(&mut input).unwrap();
             input = input.trim().to_string();
-            match input.parse::<u32>() {
-                Ok(col_move) => {
-                    if self.allows_move(col_move) {
-                        return col_move;
-                    }
+            match input.as_str() {
+                "u" => return Command::Undo,
+                "r" => return Command::Redo,
+                "n" => {
+                    println!("{}", self.to_notation());
+                    continue;
                 }
-                Err(_) => continue,
+                _ => (),
+            }
+            let cell: Vec<u32> = input.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+            let (mv, checked) = match input.strip_prefix('p').map(|col| col.trim().parse::<u32>()) {
+                Some(Ok(col)) => (Connect4Move::pop(col), self.check_pop(col, player)),
+                Some(Err(_)) => continue,
+                None => match cell[..] {
+                    [row, col] => (Connect4Move::place(row, col), self.check_place(row, col, player)),
+                    [col] => (Connect4Move::drop(col), self.check_move(col, player)),
+                    _ => continue,
+                },
+            };
+            match checked {
+                Ok(()) => return Command::Play(mv),
+                Err(error) => println!("Can't play there: {}", error),
             }
         }
     }
 
-    pub fn print_congrats(&self) {
-        if self.is_draw() {
-            println!("{}", self);
-            println!("It's a draw!");
-        } else if let Some(last_player) = self.last_player {
-            println!("{}", self);
-            println!("{} wins -- Congratulations!", last_player);
+    fn objective(&self) -> String {
+        let goal = match self.variant {
+            Variant::Pop10 => format!("keep {} of your discs", POP10_TARGET),
+            _ => format!("connect {} in a row", self.win_length),
+        };
+        if self.misere {
+            format!("Misère rules: the first to {} loses!", goal)
+        } else {
+            format!("The first to {} wins.", goal)
         }
     }
 
-    pub fn host_game(&mut self) -> String {
-        println!("Welcome to Connect Four!\n");
-        let mut game_over = false;
-        let mut moves = String::new();
-        let mut ox = 'X';
-        while !game_over {
-            println!("{}", self);
-            let col_move = self.get_player_move(ox);
-            self.perform_move(col_move, ox);
-            moves.push_str(&col_move.to_string());
-            if self.is_terminal() {
-                game_over = true;
+    fn print_congrats(&self) {
+        println!("{}", self);
+        match self.outcome() {
+            Outcome::Win(player) if self.misere => {
+                println!("{} got there first, so {} wins -- Congratulations!", self.symbol(player.other()), self.symbol(player))
             }
-            ox = if ox == 'X' { 'O' } else { 'X' };
+            Outcome::Win(player) => println!("{} wins -- Congratulations!", self.symbol(player)),
+            Outcome::Draw => println!("It's a draw!"),
+            Outcome::InProgress => (),
         }
-        self.print_congrats();
-        moves
-    
     }
 
-    pub fn host_game_AI(&mut self, difficulty: Difficulty) -> String {
-        println!("Welcome to Connect Four vs AI mode!\n");
-        let mut game_over = false;
-        let mut moves = String::new();
-        let mut ox = 'X';
-        let ai = Connect4AI::new(difficulty);
-        while !game_over {
-            println!("{}", self);
-            let col_move = {
-                if ox == 'O' {
-                    ai.best_move(self, ox)
-                } else {
-                    self.get_player_move(ox)
-                }
-            };
-            self.perform_move(col_move, ox);
-            moves.push_str(&col_move.to_string());
-            if self.is_terminal() {
-                game_over = true;
-            }
-            ox = if ox == 'X' { 'O' } else { 'X' };
+    fn commit(&mut self, mv: Connect4Move) {
+        match (mv.kind, mv.row) {
+            (MoveKind::Drop, _) => self.perform_move(mv.col),
+            (MoveKind::Pop, _) => self.perform_pop(mv.col),
+            (MoveKind::Place, Some(row)) => self.perform_place(row, mv.col),
+            (MoveKind::Place, None) => panic!("a placement needs a row"),
+        }
+    }
+
+    fn take_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    fn replay(&mut self) -> bool {
+        self.redo().is_some()
+    }
+
+    fn moves(&self) -> Vec<String> {
+        self.history.iter().map(|record| Connect4Move::from(record).to_string()).collect()
+    }
+
+    fn record_tags(&self) -> Vec<(&'static str, String)> {
+        let mut rules = format!("{:?}", self.variant);
+        if self.players > 2 {
+            rules += &format!(" {}-player", self.players);
+        }
+        if self.misere {
+            rules += " misere";
         }
-        self.print_congrats();
-        moves
-    
+        if !self.gravity {
+            rules += " no-gravity";
+        }
+        vec![
+            ("Game", "Connect 4".to_string()),
+            ("Board", format!("{}x{}", self.width, self.height)),
+            ("Rules", rules),
+            ("WinLength", self.win_length.to_string()),
+            ("Start", self.to_notation()),
+        ]
+    }
+
+    fn seats(&self) -> Vec<Player> {
+        Player::ALL[..self.players as usize].to_vec()
     }
 
+    fn name(&self, player: Player) -> String {
+        self.symbol(player).to_string()
+    }
 }
 
 impl fmt::Display for Connect4Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for row in &self.board {
-            for &cell in row {
-                write!(f, "{} ", cell)?;
-            }
-            writeln!(f)?;
-        }
-        for col in 0..self.width {
-            write!(f, "{} ", col)?;
+        game::write_grid(f, self.width, self.height, !self.gravity, |row, col| {
+            self.owner(row, col).map_or(' ', |player| self.symbol(player))
+        })?;
+        if self.variant == Variant::Pop10 {
+            writeln!(f, "{} has kept {}, {} has kept {} of {}",
+                self.symbols[0], self.kept[0], self.symbols[1], self.kept[1], POP10_TARGET)?;
         }
-        writeln!(f)?;
         Ok(())
     }
 }
 
+/// Fewest discs on the board before the solver is used, any earlier and it
+/// takes too long
+pub const SOLVER_MIN_DISCS: u32 = 8;
+
+// slots in the AI's transposition table
+const TABLE_SIZE: usize = 1 << 18;
+
+// deepest the AI searches without gravity, where every empty cell is a move
+const FREE_PLACEMENT_DEPTH: u32 = 4;
+
 #[wasm_bindgen]
 pub struct Connect4AI {
+    difficulty: Difficulty,
     depth: u32,
+    // only set for Difficulty::Mcts, which plays with it instead of searching
+    mcts: Option<Mcts<Connect4Board>>,
+    searcher: Searcher<Connect4Board>,
+    weights: Connect4Weights,
+}
+
+/// What Connect4AI's heuristic makes of each thing it looks for, so versions
+/// of it can be played against each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Connect4Weights {
+    /// each of the player's discs in the middle column or columns
+    pub center: i32,
+    /// a line of the player's discs, long enough to win
+    pub line: i32,
+    /// a line one disc short with the last cell empty
+    pub three: i32,
+    /// a line two discs short with the rest empty
+    pub two: i32,
+    /// taken off for each opponent's line one disc short
+    pub block: i32,
+    /// each disc kept in Pop 10 more than the opponent
+    pub kept: i32,
+}
+
+impl Default for Connect4Weights {
+    fn default() -> Connect4Weights {
+        Connect4Weights { center: 3, line: 100, three: 5, two: 2, block: 4, kept: 200 }
+    }
 }
 
 #[wasm_bindgen]
 impl Connect4AI {
     #[wasm_bindgen(constructor)]
     pub fn new(difficulty: Difficulty) -> Connect4AI {
-        let depth = match difficulty {
-            Difficulty::Easy => 1,
-            Difficulty::Hard => 5,
+        let (depth, mcts) = match difficulty {
+            Difficulty::Easy => (1, None),
+            Difficulty::Hard => (7, None),
+            // the search depth is only used on boards the solver can't handle
+            Difficulty::Perfect => (9, None),
+            // the depth is only reported, tree search goes as deep as it likes
+            Difficulty::Mcts => (7, Some(Mcts::new(DEFAULT_ITERATIONS, Rollout::Random))),
         };
-        Connect4AI { depth }
+        Connect4AI { difficulty, depth, mcts, searcher: Searcher::new(TABLE_SIZE), weights: Connect4Weights::default() }
     }
 
+    /// The best move for the side to move. With Difficulty::Perfect, the
+    /// standard board is solved exactly once a few discs are down. Solving the
+    /// first moves takes minutes, so those come from a deep search instead.
+    /// With more than two players the search is paranoid: it assumes all the
+    /// others play against the side to move. With Difficulty::Mcts it's the
+    /// move tree search tried most.
     #[wasm_bindgen]
-    pub fn best_move(&self, board: &mut Connect4Board, ox: char) -> u32 {
-        let maximizing_player = ox == 'X';
-        self.minimax(board, self.depth, i32::MIN, i32::MAX, maximizing_player).1
+    pub fn best_move(&mut self, board: &mut Connect4Board) -> Connect4Move {
+        let evaluate = self.evaluator();
+        if let Some(mcts) = self.mcts.as_mut() {
+            return mcts.best_move(board, &evaluate);
+        }
+        // every perfect player shares the solver and its table, which is too
+        // big to make one for each
+        if self.difficulty == Difficulty::Perfect {
+            let discs = board.occupied().count_ones();
+            if discs >= SOLVER_MIN_DISCS && !board.is_terminal() {
+                // boards the solver can't handle are searched like any other
+                if let Some(col) = solver::solve(board).ok().and_then(|solution| solution.best_move()) {
+                    return Connect4Move::drop(col);
+                }
+            }
+        }
+        self.searcher.best_move(board, self.depth_for(board), &evaluate).1
+    }
+
+    /// Searches one ply deeper at a time until `millis` milliseconds are up and
+    /// returns the best move of the last search that finished. The difficulty's
+    /// depth and solver aren't used, and Difficulty::Mcts runs as many
+    /// iterations as fit in the time instead.
+    #[wasm_bindgen]
+    pub fn best_move_with_budget(&mut self, board: &mut Connect4Board, millis: u32) -> Connect4Move {
+        self.with_budget(board, Budget::millis(millis))
+    }
+
+    /// Like best_move_with_budget, but stops after searching `nodes` positions,
+    /// so the move doesn't depend on how fast the machine is
+    #[wasm_bindgen]
+    pub fn best_move_with_node_budget(&mut self, board: &mut Connect4Board, nodes: u32) -> Connect4Move {
+        self.with_budget(board, Budget::nodes(nodes as u64))
+    }
+
+    /// Sets how many iterations best_move runs with Difficulty::Mcts
+    #[wasm_bindgen]
+    pub fn set_iterations(&mut self, iterations: u32) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_iterations(iterations);
+        }
+    }
+
+    /// Sets how Difficulty::Mcts plays games out, at random or guided by the
+    /// heuristic
+    #[wasm_bindgen]
+    pub fn set_rollout(&mut self, rollout: Rollout) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_rollout(rollout);
+        }
+    }
+
+    /// The AI's settings as a JavaScript object, see BotConfig
+    #[wasm_bindgen]
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(self)?)
+    }
+
+    /// Sets up an AI with settings written by to_js
+    #[wasm_bindgen]
+    pub fn from_js(value: JsValue) -> Result<Connect4AI, JsValue> {
+        Ok(serde_wasm_bindgen::from_value(value)?)
+    }
+}
+
+impl Serialize for Connect4AI {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BotConfig { difficulty: self.difficulty }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Connect4AI {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Connect4AI, D::Error> {
+        BotConfig::deserialize(deserializer).map(|config| Connect4AI::new(config.difficulty))
+    }
+}
+
+impl Connect4AI {
+    /// Searches `depth` plies ahead instead of the difficulty's depth
+    pub fn set_depth(&mut self, depth: u32) {
+        self.depth = depth;
+    }
+
+    /// Scores positions with `weights` from now on. Scores kept from earlier
+    /// searches were worked out with the old ones, so they're forgotten.
+    pub fn set_weights(&mut self, weights: Connect4Weights) {
+        self.weights = weights;
+        self.searcher = Searcher::new(TABLE_SIZE);
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.clear();
+        }
+    }
+
+    fn with_budget(&mut self, board: &mut Connect4Board, budget: Budget) -> Connect4Move {
+        let evaluate = self.evaluator();
+        match self.mcts.as_mut() {
+            Some(mcts) => mcts.best_move_with_budget(board, budget, &evaluate),
+            None => self.searcher.best_move_with_budget(board, budget, &evaluate),
+        }
     }
-    
-    fn evaluate_window(&self, window: &[char], player: char) -> i32 {
-        let mut score = 0;
-        let opponent = if player == 'X' { 'O' } else { 'X' };
 
-        let player_count = window.iter().filter(|&&ox| ox == player).count();
-        let opponent_count = window.iter().filter(|&&ox| ox == opponent).count();
-        let empty_count = window.iter().filter(|&&ox| ox == ' ').count();
+    // how deep to search `board`, shallower when discs can go on any cell
+    fn depth_for(&self, board: &Connect4Board) -> u32 {
+        if board.gravity() { self.depth } else { cmp::min(self.depth, FREE_PLACEMENT_DEPTH) }
+    }
+
+    // score_position with this AI's weights, for the searcher
+    fn evaluator(&self) -> impl Fn(&Connect4Board, Player) -> i32 {
+        let weights = self.weights;
+        move |board, player| Self::score_position(board, player, &weights)
+    }
 
-        if player_count == 4 {
-            score += 100;
-        } else if player_count == 3 && empty_count == 1 {
-            score += 5;
-        } else if player_count == 2 && empty_count == 2 {
-            score += 2;
+    fn evaluate_window(length: u32, player_count: u32, opponent_count: u32, weights: &Connect4Weights) -> i32 {
+        let mut score = 0;
+        let empty_count = length - player_count - opponent_count;
+
+        if player_count == length {
+            score += weights.line;
+        } else if empty_count == 1 && opponent_count == 0 {
+            score += weights.three;
+        } else if empty_count == 2 && opponent_count == 0 {
+            score += weights.two;
         }
 
-        if opponent_count == 3 && empty_count == 1 {
-            score -= 4;
+        if opponent_count == length - 1 && empty_count == 1 {
+            score -= weights.block;
         }
 
         score
     }
 
-    fn score_position(&self, board: &Connect4Board, maximizing_player: bool) -> i32 {
+    /// Heuristic value of `board` for `player`, used where the search stops
+    fn score_position(board: &Connect4Board, player: Player, weights: &Connect4Weights) -> i32 {
         let mut score = 0;
-        let player = if maximizing_player { 'X' } else { 'O' };
+        let mine = board.discs(player);
+        // paranoid, everyone else's discs count as one opponent's
+        let theirs = board.occupied() & !mine;
 
         // Score center column: the more pieces in the center, the better
-        let center_array: Vec<char> = board.board.iter().map(|row| row[board.width as usize / 2]).collect();
-        let center_count = center_array.iter().filter(|&&ox| ox == player).count() as i32;
-        score += center_count * 3;
-
-        // Score horizontal: the more pieces in a row, the better
-        for row in &board.board {
-            for window in row.windows(4) {
-                score += self.evaluate_window(window, player);
-            }
-        }
+        let center_count = (mine & board.center_mask()).count_ones() as i32;
+        score += center_count * weights.center;
 
-        // Score vertical: the more pieces in a column, the better
-        for col in 0..board.width {
-            let col_array: Vec<char> = board.board.iter().map(|row| row[col as usize]).collect();
-            for window in col_array.windows(4) {
-                score += self.evaluate_window(window, player);
-            }
+        // Score every line: the more pieces in a line, the better
+        for &window in board.windows() {
+            score += Self::evaluate_window(board.win_length, (mine & window).count_ones(), (theirs & window).count_ones(), weights);
         }
 
-        // Score positive sloped diagonal: the more pieces in a diagonal, the better
-        for row in 0..(board.height as usize - 3) {
-            for col in 0..(board.width as usize - 3) {
-                let window: Vec<char> = (0..4).map(|i| board.board[row  + i][col + i]).collect();
-                score += self.evaluate_window(&window, player);
+        // In Pop 10 every disc kept is worth more than any line
+        score += (board.kept(player) as i32 - board.kept(player.other()) as i32) * weights.kept;
+
+        // In misère everything that would help the usual objective hurts
+        if board.misere() { -score } else { score }
+    }
+}
+
+impl Bot<Connect4Board> for Connect4AI {
+    fn choose_move(&mut self, board: &mut Connect4Board) -> Connect4Move {
+        self.best_move(board)
+    }
+
+    fn choose_move_with_budget(&mut self, board: &mut Connect4Board, millis: u32) -> Connect4Move {
+        self.best_move_with_budget(board, millis)
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    fn depth(&self, board: &Connect4Board) -> u32 {
+        self.depth_for(board)
+    }
+
+    /// Only searches, even with Difficulty::Perfect, so there's a score and
+    /// a line to report at every depth. Tree search has no depths, so with
+    /// Difficulty::Mcts there's one report once its iterations or budget run
+    /// out, and any depth limit means its usual number of iterations.
+    fn analyze(&mut self, board: &mut Connect4Board, max_depth: u32, budget: Budget, report: &mut dyn FnMut(&SearchInfo<Connect4Move>)) -> Connect4Move {
+        let evaluate = self.evaluator();
+        match self.mcts.as_mut() {
+            Some(mcts) => {
+                let iterations = if max_depth == u32::MAX { u32::MAX } else { mcts.iterations() };
+                mcts.search(board, iterations, budget, &evaluate, report)
             }
+            None => self.searcher.deepen(board, max_depth, budget, &evaluate, report),
         }
+    }
+}
 
-        // Score negative sloped diagonal: the more pieces in a diagonal, the better
-        for row in 3..board.height as usize {
-            for col in 0..(board.width as usize - 3) {
-                let window: Vec<char> = (0..4).map(|i| board.board[row - i][col + i]).collect();
-                score += self.evaluate_window(&window, player);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_off_the_board_are_empty() {
+        let board = Connect4Board::from_notation("7/7/7/7/7/3X3 O").unwrap();
+        assert_eq!(board.owner(5, 3), Some(Player::One));
+        assert_eq!(board.get(5, 3), Cell::X);
+        assert_eq!(board.owner(6, 3), None);
+        assert_eq!(board.owner(0, 7), None);
+        assert_eq!(board.get(u32::MAX, u32::MAX), Cell::Empty);
+    }
+
+    fn popout() -> Connect4Board {
+        Connect4Board::with_variant(7, 6, 4, Variant::PopOut).unwrap()
+    }
+
+    #[test]
+    fn popping_drops_the_column() {
+        let mut board = popout();
+        board.try_play(3, Player::One).unwrap();
+        assert_eq!(board.try_move(Connect4Move::pop(3), Player::Two).unwrap_err(), MoveError::NotYourDisc);
+        board.try_play(3, Player::Two).unwrap();
+        board.try_move(Connect4Move::pop(3), Player::One).unwrap();
+        assert_eq!(board.owner(5, 3), Some(Player::Two));
+        assert_eq!(board.owner(4, 3), None);
+        assert_eq!(board.side_to_move(), Player::Two);
+    }
+
+    #[test]
+    fn classic_boards_cant_pop() {
+        let mut board = Connect4Board::new(BoardSize::Standard);
+        board.try_play(3, Player::One).unwrap();
+        board.try_play(4, Player::Two).unwrap();
+        assert_eq!(board.try_move(Connect4Move::pop(3), Player::One).unwrap_err(), MoveError::PopNotAllowed);
+    }
+
+    // X's pop in column 3 drops a row of four for each player into place
+    fn pop_completing_both_lines(misere: bool) -> Connect4Board {
+        let mut board = popout();
+        board.set_misere(misere);
+        let drops = [(3, Player::One), (0, Player::Two), (0, Player::One), (1, Player::Two), (1, Player::One),
+            (2, Player::Two), (2, Player::One), (3, Player::Two), (3, Player::One), (6, Player::Two)];
+        for (col, player) in drops {
+            board.try_play(col, player).unwrap();
         }
-        score
+        assert_eq!(Game::outcome(&board), Outcome::InProgress);
+        board.try_move(Connect4Move::pop(3), Player::One).unwrap();
+        board
     }
 
+    #[test]
+    fn a_pop_completing_both_lines_wins_for_the_popper() {
+        assert_eq!(Game::outcome(&pop_completing_both_lines(false)), Outcome::Win(Player::One));
+    }
 
-    fn minimax(&self, board: &mut Connect4Board, depth: u32, alpha: i32, beta: i32, maximizing_player: bool) -> (i32, u32) {
-        let mut best_score = if maximizing_player { i32::MIN } else { i32::MAX };
-        let mut alpha = alpha;
-        let mut beta = beta;
-        if depth == 0 || board.is_terminal() {
-            best_score = { 
-                if board.is_terminal() { // win, lose, or draw
-                    board.game_value()
-                } else { // evaluate the odds of player winning in this position
-                    self.score_position(board, maximizing_player) 
-                }
-            };
-            return (best_score, 0)
+    #[test]
+    fn misere_hands_the_pop_to_the_other_player() {
+        assert_eq!(Game::outcome(&pop_completing_both_lines(true)), Outcome::Win(Player::Two));
+    }
+
+    #[test]
+    fn pop10_keys_count_kept_and_pending_discs() {
+        let mut board = Connect4Board::with_variant(7, 6, 4, Variant::Pop10).unwrap();
+        for col in [0, 1, 2, 3] {
+            board.try_play(col, board.side_to_move()).unwrap();
+        }
+        let mut kept = board.clone();
+        kept.kept[0] = 1;
+        let mut more = board.clone();
+        more.kept[0] = 2;
+        let mut pending = board.clone();
+        pending.pending = Some(2);
+        let keys = [&board, &kept, &more, &pending].map(Game::zobrist_key);
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[i + 1..].contains(key));
         }
+        assert_ne!(kept.position_key(), board.position_key());
+    }
 
-        let mut best_move = board.available_moves().choose(&mut rand::thread_rng()).unwrap().clone();
+    #[test]
+    fn every_side_to_move_has_its_own_key() {
+        let mut board = Connect4Board::new(BoardSize::Standard);
+        board.set_players(4).unwrap();
+        let keys: Vec<u64> = Player::ALL.iter().map(|&player| {
+            let mut turn = board.clone();
+            turn.side_to_move = player;
+            turn.zobrist_key() ^ turn.side_key()
+        }).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!keys[i + 1..].contains(key));
+        }
+    }
 
-        for &i in board.available_moves().iter() {
-            board.perform_move(i, if maximizing_player { 'X' } else { 'O' });
-            let score = self.minimax(board, depth - 1, alpha, beta, !maximizing_player).0;
-            board.undo_move(i);
-            if maximizing_player {
-                if score > best_score {
-                    best_move = i;
-                    best_score = score;
-                }
-                alpha = cmp::max(alpha, best_score);
-                if alpha >= beta {
-                    break;
-                }
-            } else {
-                if score < best_score {
-                    best_move = i;
-                    best_score = score;
-                }
-                beta = cmp::min(beta, best_score);
-                if alpha >= beta {
-                    break;
-                }
+    #[test]
+    fn players_and_symbols_are_set_before_the_game() {
+        let mut board = Connect4Board::new(BoardSize::Standard);
+        board.set_players(3).unwrap();
+        assert_eq!(board.set_players(5), Err(MoveError::PlayersOutOfRange));
+        board.set_symbols(&['O', 'X']).unwrap();
+        assert_eq!((board.symbol(Player::One), board.symbol(Player::Two)), ('O', 'X'));
+        for symbol in ['X', ' ', '7', '/', '\n'] {
+            assert_eq!(board.set_symbol(Player::Three, symbol), Err(MoveError::InvalidSymbol));
+        }
+        board.set_symbol(Player::Three, '#').unwrap();
 
-            }
+        board.try_play(3, Player::One).unwrap();
+        assert_eq!(board.set_players(2), Err(MoveError::GameAlreadyStarted));
+        assert_eq!(board.set_symbol(Player::One, '@'), Err(MoveError::GameAlreadyStarted));
+        assert_eq!((board.players(), board.symbol(Player::One)), (3, 'O'));
+    }
+
+    #[test]
+    fn boards_come_back_from_json_as_they_were() {
+        let mut board = popout();
+        board.set_misere(true);
+        for col in [3, 3, 4] {
+            board.try_play(col, board.side_to_move()).unwrap();
         }
-        
-        (best_score, best_move.clone())
+        board.try_play(2, Player::Two).unwrap();
+        board.try_move(Connect4Move::pop(3), Player::One).unwrap();
+        board.undo();
+
+        let json = board.to_json();
+        let loaded = Connect4Board::from_json(&json).unwrap();
+        assert_eq!(loaded.to_notation(), board.to_notation());
+        assert_eq!(Game::zobrist_key(&loaded), Game::zobrist_key(&board));
+        assert_eq!(Connect4State::from(loaded.clone()), Connect4State::from(board));
+        assert_eq!(loaded.to_json(), json);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn loading_fails_on_an_illegal_move() {
+        let mut state = Connect4State::from(Connect4Board::new(BoardSize::Standard));
+        state.history = vec![Connect4Move::drop(3), Connect4Move::drop(7)];
+        assert_eq!(Connect4Board::try_from(state).err(), Some(NotationError::IllegalMove(2, MoveError::ColumnOutOfRange)));
+    }
+}
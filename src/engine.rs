@@ -0,0 +1,317 @@
+//! A line-based engine protocol over stdin and stdout, like chess's UCI
+//!
+//! `connect4 engine` plays whichever board its options set up, so GUIs and
+//! tournament managers can run the engines against each other. Each line
+//! read is a command, and the engine answers on stdout:
+//!
+//! ```text
+//! uci                              id name connect4 0.1.0, then uciok
+//! isready                          readyok
+//! newgame                          back to the start, forgetting past searches
+//! position startpos moves 4453     the start position, then moves 4, 4, 5, 3
+//! position notation 7/7/7/7/7/3X3 O moves 3
+//! go depth 9 | movetime 500 | nodes 100000
+//!                                  info depth 1 score 4 nodes 8 pv 3
+//!                                  ...
+//!                                  bestmove 3
+//! quit
+//! ```
+//!
+//! Moves are written as game records write them, separated by spaces. A run
+//! of them may also be written together the way `from_notation` reads them,
+//! like 4453 or 2T3O. A bare `go` searches as deep as the engine's level
+//! does. A position in notation is played by the rules the engine was
+//! started with and has to be the size of its board. Scores are from the side
+//! to move's point of view, `win` or `loss` once the result is forced.
+//! Anything the engine can't do is answered with `info string` and why.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{stdin, BufRead};
+use crate::board::{Difficulty, MoveError, NotationError, Player};
+use crate::budget::Budget;
+use crate::connect4::{Connect4AI, Connect4Board, Connect4Move, Connect4State};
+use crate::game::{Game, Outcome};
+use crate::notation;
+use crate::otto::{OttoMove, OttoState, TootOttoBoard};
+use crate::ottobot::OttoBot;
+use crate::search::{Bot, SearchInfo};
+
+/// What the engine needs from a board on top of what the search does
+pub trait EngineGame: Game + Clone {
+    type Ai: Bot<Self>;
+
+    fn new_ai(difficulty: Difficulty) -> Self::Ai;
+
+    /// Reads a position the way the board's `from_notation` does, but
+    /// played by this board's rules. A position must be this board's size,
+    /// and moves on their own are played from this board's position.
+    fn read_position(&self, notation: &str) -> Result<Self, NotationError>;
+
+    /// Reads one move as game records write it, or a run of them written
+    /// together, checked against this board's size
+    fn read_moves(&self, text: &str) -> Option<Vec<Self::Move>>;
+
+    /// Plays `mv` for the side to move, if it's legal
+    fn try_play_move(&mut self, mv: Self::Move) -> Result<(), MoveError>;
+
+    fn write_move(mv: Self::Move) -> String;
+}
+
+struct Engine<G: EngineGame> {
+    start: G,
+    board: G,
+    level: Difficulty,
+    ai: G::Ai,
+}
+
+/// Answers commands from stdin until `quit` or the end of the input. `start`
+/// is the position `newgame` and `position startpos` go back to, and `level`
+/// sets how deep a bare `go` searches.
+pub fn run_engine<G: EngineGame>(start: G, level: Difficulty) {
+    let mut engine = Engine { board: start.clone(), start, level, ai: G::new_ai(level) };
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.split_first() {
+            None => (),
+            Some((&"quit", _)) => break,
+            Some((&command, args)) => {
+                if let Err(problem) = engine.command(command, args) {
+                    println!("info string {}", problem);
+                }
+            }
+        }
+    }
+}
+
+impl<G: EngineGame> Engine<G> {
+    fn command(&mut self, command: &str, args: &[&str]) -> Result<(), String> {
+        match command {
+            "uci" => {
+                println!("id name connect4 {}", env!("CARGO_PKG_VERSION"));
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "newgame" => {
+                self.board = self.start.clone();
+                self.ai = G::new_ai(self.level);
+            }
+            "position" => self.board = self.position(args)?,
+            "go" => self.go(args)?,
+            _ => return Err(format!("unknown command {}", command)),
+        }
+        Ok(())
+    }
+
+    // reads `startpos` or `notation <position>`, then the moves after `moves`
+    fn position(&self, args: &[&str]) -> Result<G, String> {
+        let split = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+        let (start, moves) = (&args[..split], args.get(split + 1..).unwrap_or(&[]));
+        let mut board = match start.split_first() {
+            Some((&"startpos", [])) => self.start.clone(),
+            Some((&"notation", position)) if !position.is_empty() => {
+                self.start.read_position(&position.join(" ")).map_err(|error| format!("can't read that position: {}", error))?
+            }
+            _ => return Err("position needs startpos or notation and a position".to_string()),
+        };
+        for &text in moves {
+            let moves = board.read_moves(text).ok_or_else(|| format!("can't read the move {}", text))?;
+            for mv in moves {
+                board.try_play_move(mv).map_err(|error| format!("can't play {}: {}", G::write_move(mv), error))?;
+            }
+        }
+        Ok(board)
+    }
+
+    // searches the current position within the limits in `args`
+    fn go(&mut self, args: &[&str]) -> Result<(), String> {
+        let mut max_depth = None;
+        let mut budget = Budget::unlimited();
+        for pair in args.chunks(2) {
+            let (limit, value) = match pair {
+                [limit, value] => (*limit, value.parse::<u32>().map_err(|_| format!("{} can't be {}", limit, value))?),
+                _ => return Err(format!("{} needs a value after it", pair[0])),
+            };
+            match limit {
+                "depth" => max_depth = Some(value),
+                "movetime" => budget = Budget::millis(value),
+                "nodes" => budget = Budget::nodes(value as u64),
+                _ => return Err(format!("go can't be limited by {}", limit)),
+            }
+        }
+        if self.board.outcome() != Outcome::InProgress || self.board.legal_moves().is_empty() {
+            println!("bestmove none");
+            return Ok(());
+        }
+
+        // with only a time or node limit, search until it runs out
+        let max_depth = match (max_depth, args.is_empty()) {
+            (Some(depth), _) => depth,
+            (None, true) => self.ai.depth(&self.board),
+            (None, false) => u32::MAX,
+        };
+        let mv = self.ai.analyze(&mut self.board, max_depth, budget, &mut |info| println!("{}", InfoLine::<G>(info)));
+        println!("bestmove {}", G::write_move(mv));
+        Ok(())
+    }
+}
+
+// writes a search report as an info line
+struct InfoLine<'a, G: EngineGame>(&'a SearchInfo<G::Move>);
+
+impl<G: EngineGame> fmt::Display for InfoLine<'_, G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let info = self.0;
+        write!(f, "info depth {} score ", info.depth)?;
+        match info.score {
+            i32::MAX => write!(f, "win")?,
+            i32::MIN => write!(f, "loss")?,
+            score => write!(f, "{}", score)?,
+        }
+        write!(f, " nodes {} pv", info.nodes)?;
+        for &mv in &info.pv {
+            write!(f, " {}", G::write_move(mv))?;
+        }
+        Ok(())
+    }
+}
+
+// widest board whose columns are all one digit, so moves can run together
+const DIGIT_COLUMNS: u32 = 10;
+
+// plays the moves in `notation`, run together, on a copy of `start`
+fn play_from<G: EngineGame>(start: &G, notation: &str) -> Result<G, NotationError> {
+    let mut board = start.clone();
+    let moves = start.read_moves(notation).ok_or(NotationError::Unreadable)?;
+    for (number, mv) in (1..).zip(moves) {
+        board.try_play_move(mv).map_err(|error| NotationError::IllegalMove(number, error))?;
+    }
+    Ok(board)
+}
+
+impl EngineGame for Connect4Board {
+    type Ai = Connect4AI;
+
+    fn new_ai(difficulty: Difficulty) -> Connect4AI {
+        Connect4AI::new(difficulty)
+    }
+
+    fn read_position(&self, notation: &str) -> Result<Connect4Board, NotationError> {
+        let notation = notation.trim();
+        if !notation::is_position(notation) {
+            return play_from(self, notation);
+        }
+        let board = Connect4Board::try_from(Connect4State {
+            start: notation.to_string(),
+            history: Vec::new(),
+            redone: Vec::new(),
+            ..Connect4State::from(self.clone())
+        })?;
+        if (board.width(), board.height()) != (self.width(), self.height()) {
+            return Err(NotationError::WrongSize);
+        }
+        Ok(board)
+    }
+
+    fn read_moves(&self, text: &str) -> Option<Vec<Connect4Move>> {
+        if text.len() > 1 && self.width() <= DIGIT_COLUMNS && text.chars().all(|c| c.is_ascii_digit()) {
+            return text.chars().map(|c| c.to_digit(10).map(Connect4Move::drop)).collect();
+        }
+        Connect4Move::parse(text).map(|mv| vec![mv])
+    }
+
+    fn try_play_move(&mut self, mv: Connect4Move) -> Result<(), MoveError> {
+        self.try_move(mv, self.side_to_move()).map(|_| ())
+    }
+
+    fn write_move(mv: Connect4Move) -> String {
+        mv.to_string()
+    }
+}
+
+impl EngineGame for TootOttoBoard {
+    type Ai = OttoBot;
+
+    fn new_ai(difficulty: Difficulty) -> OttoBot {
+        // the bot plays whichever side is to move
+        OttoBot::new(difficulty, Player::One)
+    }
+
+    fn read_position(&self, notation: &str) -> Result<TootOttoBoard, NotationError> {
+        let notation = notation.trim();
+        if !notation::is_position(notation) {
+            return play_from(self, notation);
+        }
+        let board = TootOttoBoard::try_from(OttoState {
+            start: notation.to_string(),
+            history: Vec::new(),
+            redone: Vec::new(),
+            ..OttoState::from(self.clone())
+        })?;
+        if (board.width(), board.height()) != (self.width(), self.height()) {
+            return Err(NotationError::WrongSize);
+        }
+        Ok(board)
+    }
+
+    fn read_moves(&self, text: &str) -> Option<Vec<OttoMove>> {
+        if let Some(mv) = OttoMove::parse(text) {
+            return Some(vec![mv]);
+        }
+        if self.width() > DIGIT_COLUMNS {
+            return None;
+        }
+        let chars: Vec<char> = text.chars().collect();
+        chars.chunks(2).map(|pair| OttoMove::parse(&pair.iter().collect::<String>())).collect()
+    }
+
+    fn try_play_move(&mut self, mv: OttoMove) -> Result<(), MoveError> {
+        self.try_move(mv, self.side_to_move()).map(|_| ())
+    }
+
+    fn write_move(mv: OttoMove) -> String {
+        mv.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardSize;
+    use crate::connect4::Variant;
+
+    #[test]
+    fn positions_keep_the_engines_rules() {
+        let mut start = Connect4Board::with_variant(7, 6, 5, Variant::PopOut).unwrap();
+        start.set_misere(true);
+        let board = start.read_position("7/7/7/7/7/3X3 O").unwrap();
+        assert_eq!(board.win_length(), 5);
+        assert_eq!(board.variant(), Variant::PopOut);
+        assert!(board.misere());
+        assert_eq!(board.to_notation(), "7/7/7/7/7/3X3 O");
+
+        let mut start = Connect4Board::new(BoardSize::Standard);
+        start.set_players(3).unwrap();
+        assert_eq!(start.read_position("7/7/7/7/7/3X3 O").unwrap().players(), 3);
+    }
+
+    #[test]
+    fn positions_of_another_size_are_refused() {
+        let start = Connect4Board::with_dimensions(8, 7).unwrap();
+        assert_eq!(start.read_position("7/7/7/7/7/3X3 O").err(), Some(NotationError::WrongSize));
+        let otto = TootOttoBoard::with_dimensions(7, 5).unwrap();
+        assert_eq!(otto.read_position("6/6/6/Ot4 O").err(), Some(NotationError::WrongSize));
+    }
+
+    #[test]
+    fn moves_are_played_from_the_start() {
+        let start = Connect4Board::with_dimensions(8, 7).unwrap();
+        let board = start.read_position("77").unwrap();
+        assert_eq!(board.to_notation(), "8/8/8/8/8/7O/7X X");
+        assert_eq!(start.read_position("78").err(), Some(NotationError::IllegalMove(2, MoveError::ColumnOutOfRange)));
+    }
+}
@@ -0,0 +1,67 @@
+//! What the search engine and the CLI game loop need to know about a game
+//!
+//! Connect 4 and TOOT and OTTO both implement `Game`, so one alpha-beta search
+//! (`search::Searcher`) and one terminal game loop (`cli::host_game`) drive both.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome<P> {
+    InProgress,
+    Win(P),
+    Draw,
+}
+
+pub trait Game: fmt::Display {
+    type Move: Copy + PartialEq + fmt::Debug;
+    type Player: Copy + PartialEq + fmt::Debug;
+
+    /// The player whose turn it is
+    fn side_to_move(&self) -> Self::Player;
+
+    /// Every move the side to move may make from this position
+    fn legal_moves(&self) -> Vec<Self::Move>;
+
+    /// Plays `mv` for the side to move, for the search. It goes into the
+    /// board's history like any other move, but moves taken back with the
+    /// board's own undo stay redoable.
+    fn play(&mut self, mv: Self::Move);
+
+    /// Takes back `mv`, which must be the last move played
+    fn undo(&mut self, mv: Self::Move);
+
+    fn outcome(&self) -> Outcome<Self::Player>;
+
+    /// Zobrist hash of the pieces on the board, not of whose turn it is
+    fn zobrist_key(&self) -> u64;
+
+    /// Zobrist key of whose turn it is, so the search can tell the same pieces
+    /// with another player to move apart
+    fn side_key(&self) -> u64;
+}
+
+/// The middle column of a board `width` columns wide, or the middle two if
+/// `width` is even
+pub fn center_columns(width: u32) -> RangeInclusive<u32> {
+    (width - 1) / 2..=width / 2
+}
+
+/// Writes a board one row per line, top row first, with the column numbers
+/// underneath and, if `row_labels` is set, the row numbers down the right.
+/// `cell(row, col)` counts rows from the top.
+pub fn write_grid(f: &mut fmt::Formatter, width: u32, height: u32, row_labels: bool, cell: impl Fn(u32, u32) -> char) -> fmt::Result {
+    for row in 0..height {
+        for col in 0..width {
+            write!(f, "{} ", cell(row, col))?;
+        }
+        if row_labels {
+            write!(f, " {}", row)?;
+        }
+        writeln!(f)?;
+    }
+    for col in 0..width {
+        write!(f, "{} ", col)?;
+    }
+    writeln!(f)
+}
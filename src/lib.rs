@@ -1,6 +1,19 @@
 mod connect4;
+mod args;
 mod board;
+mod budget;
 mod cli;
+mod engine;
+mod game;
+mod mcts;
+mod notation;
 mod otto;
 mod ottobot;
+mod ratings;
+mod record;
+mod search;
+mod solver;
+mod tournament;
+mod tt;
+mod zobrist;
 
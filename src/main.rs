@@ -1,10 +1,31 @@
 mod connect4;
+mod args;
 mod board;
+mod budget;
 mod cli;
+mod engine;
+mod game;
+mod mcts;
+mod notation;
 mod otto;
 mod ottobot;
-use cli::run_cli;
+mod ratings;
+mod record;
+mod search;
+mod solver;
+mod tournament;
+mod tt;
+mod zobrist;
+use cli::{run_args, run_cli};
+use std::env;
+use std::process;
 
 fn main() {
-    run_cli();
-}
\ No newline at end of file
+    // with no arguments, choose from the menus
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        run_cli();
+    } else {
+        process::exit(run_args(&args));
+    }
+}
@@ -0,0 +1,420 @@
+//! Monte Carlo tree search over any `Game`
+//!
+//! Each iteration walks down the tree picking children by UCT, adds one new
+//! position, plays the game out from there and counts the result on every
+//! position it passed through. A rollout is random, or heuristic: it takes a
+//! win when there is one and otherwise mostly plays the move the game's
+//! heuristic likes best. The move played in the end is the one tried most.
+//!
+//! Results are counted for the player who made the move into each position,
+//! a win as 1 and a draw as a half, so with more than two players each one
+//! plays for themselves. The tree is kept between moves and picked up again
+//! from whichever position the game has reached, as long as it's in the tree.
+
+use std::f64::consts::SQRT_2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use wasm_bindgen::prelude::*;
+use crate::budget::Budget;
+use crate::game::{Game, Outcome};
+use crate::search::SearchInfo;
+
+/// Iterations a search runs unless it's set up otherwise
+pub const DEFAULT_ITERATIONS: u32 = 20_000;
+
+// how much UCT favours trying moves it knows little about
+const EXPLORATION: f64 = SQRT_2;
+
+// positions the tree holds at most, past that iterations only play out
+const MAX_NODES: usize = 1 << 20;
+
+// how many plies past the root the next search looks for its position
+const REUSE_PLIES: u32 = 4;
+
+// rollouts this long are called draws, popping discs can go on forever
+const MAX_ROLLOUT: u32 = 200;
+
+// how often a heuristic rollout plays a random move anyway
+const ROLLOUT_RANDOMNESS: f64 = 0.2;
+
+// an expected result of 1 is reported as this score, 0 as minus it
+const SCORE_SCALE: f64 = 1000.0;
+
+/// How rollouts pick their moves
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rollout {
+    Random,
+    Heuristic,
+}
+
+#[derive(Clone)]
+struct Node<M, P> {
+    // the move into this position and who played it, neither at the root
+    mv: Option<M>,
+    mover: Option<P>,
+    // to find the position again when the tree is reused
+    key: u64,
+    side_to_move: P,
+    children: Vec<usize>,
+    // shuffled, a child is made from the last one next
+    untried: Vec<M>,
+    visits: u32,
+    // the mover's points over every visit
+    points: f64,
+}
+
+pub struct Mcts<G: Game> {
+    // the root is always the first node
+    nodes: Vec<Node<G::Move, G::Player>>,
+    iterations: u32,
+    rollout: Rollout,
+    // positions played through since the last search started
+    positions: u64,
+}
+
+impl<G: Game> Mcts<G> {
+    /// Creates a search that runs `iterations` iterations a move
+    pub fn new(iterations: u32, rollout: Rollout) -> Mcts<G> {
+        Mcts { nodes: Vec::new(), iterations, rollout, positions: 0 }
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations;
+    }
+
+    /// Rolls out with `rollout` from now on. The tree was grown with the old
+    /// rollouts, so it's thrown away.
+    pub fn set_rollout(&mut self, rollout: Rollout) {
+        self.rollout = rollout;
+        self.clear();
+    }
+
+    /// Forgets the tree, the next search starts from nothing
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Runs the set number of iterations and returns the most tried move for
+    /// the side to move. Heuristic rollouts score moves with
+    /// `evaluate(game, player)`, `player` being the side making the move.
+    pub fn best_move<F>(&mut self, game: &mut G, evaluate: &F) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        self.search(game, self.iterations, Budget::unlimited(), evaluate, &mut |_| ())
+    }
+
+    /// Runs iterations until `budget` runs out, however many that is
+    pub fn best_move_with_budget<F>(&mut self, game: &mut G, budget: Budget, evaluate: &F) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        self.search(game, u32::MAX, budget, evaluate, &mut |_| ())
+    }
+
+    /// Runs up to `iterations` iterations or until `budget` runs out, then
+    /// calls `report` with the most tried line. Its depth is the line's length
+    /// and its score the first move's expected result, from -1000 for a sure
+    /// loss to 1000 for a sure win.
+    pub fn search<F>(&mut self, game: &mut G, iterations: u32, mut budget: Budget, evaluate: &F, report: &mut dyn FnMut(&SearchInfo<G::Move>)) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        self.set_root(game);
+        self.positions = 0;
+
+        // one iteration always runs so there is a move to return
+        let mut done = 0;
+        while done == 0 || (done < iterations && !budget.exhausted()) {
+            self.iterate(game, &mut budget, evaluate);
+            done += 1;
+        }
+
+        let info = self.info();
+        report(&info);
+        *info.pv.first().expect("no legal moves")
+    }
+
+    // keeps the part of the tree under the game's position, or starts again
+    // if that position isn't in it
+    fn set_root(&mut self, game: &G) {
+        let (key, side) = (game.zobrist_key(), game.side_to_move());
+        let mut level = if self.nodes.is_empty() { Vec::new() } else { vec![0] };
+        for _ in 0..=REUSE_PLIES {
+            let found = level.iter().copied().find(|&index| {
+                let node = &self.nodes[index];
+                node.key == key && node.side_to_move == side
+            });
+            if let Some(index) = found {
+                self.reroot(index);
+                // the key could match some other position, so make sure it's
+                // the same moves
+                let node = &self.nodes[0];
+                let mut moves: Vec<G::Move> = node.children.iter().filter_map(|&child| self.nodes[child].mv).collect();
+                moves.extend(node.untried.iter().copied());
+                let legal = game.legal_moves();
+                if moves.len() == legal.len() && legal.iter().all(|mv| moves.contains(mv)) {
+                    return;
+                }
+                break;
+            }
+            level = level.iter().flat_map(|&index| self.nodes[index].children.iter().copied()).collect();
+        }
+        self.nodes.clear();
+        self.nodes.push(self.new_node(game, None, None));
+    }
+
+    // makes `root` the first node, dropping every node not under it
+    fn reroot(&mut self, root: usize) {
+        if root == 0 {
+            return;
+        }
+        let mut order = vec![root];
+        let mut next = 0;
+        while next < order.len() {
+            order.extend(self.nodes[order[next]].children.iter().copied());
+            next += 1;
+        }
+        let mut moved_to = vec![usize::MAX; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            moved_to[old] = new;
+        }
+        self.nodes = order.iter().map(|&old| {
+            let mut node = self.nodes[old].clone();
+            node.children.iter_mut().for_each(|child| *child = moved_to[*child]);
+            node
+        }).collect();
+        self.nodes[0].mv = None;
+        self.nodes[0].mover = None;
+    }
+
+    fn new_node(&self, game: &G, mv: Option<G::Move>, mover: Option<G::Player>) -> Node<G::Move, G::Player> {
+        let mut untried = match game.outcome() {
+            Outcome::InProgress => game.legal_moves(),
+            _ => Vec::new(),
+        };
+        untried.shuffle(&mut rand::thread_rng());
+        Node {
+            mv,
+            mover,
+            key: game.zobrist_key(),
+            side_to_move: game.side_to_move(),
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            points: 0.0,
+        }
+    }
+
+    // one walk down the tree, a new node, a rollout and the result counted
+    fn iterate<F>(&mut self, game: &mut G, budget: &mut Budget, evaluate: &F)
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        let mut path = vec![0];
+        let mut played = Vec::new();
+        loop {
+            let index = *path.last().unwrap();
+            self.positions += 1;
+            budget.tick();
+            if game.outcome() != Outcome::InProgress {
+                break;
+            }
+            let mover = game.side_to_move();
+            if !self.nodes[index].untried.is_empty() && self.nodes.len() < MAX_NODES {
+                let mv = self.nodes[index].untried.pop().unwrap();
+                game.play(mv);
+                played.push(mv);
+                let child = self.new_node(game, Some(mv), Some(mover));
+                self.nodes.push(child);
+                let child = self.nodes.len() - 1;
+                self.nodes[index].children.push(child);
+                path.push(child);
+                break;
+            }
+            match self.select(index) {
+                Some(child) => {
+                    let mv = self.nodes[child].mv.unwrap();
+                    game.play(mv);
+                    played.push(mv);
+                    path.push(child);
+                }
+                None => break,
+            }
+        }
+
+        let outcome = self.roll_out(game, budget, evaluate);
+        for &index in &path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.points += match (outcome, node.mover) {
+                (Outcome::Win(winner), Some(mover)) if winner == mover => 1.0,
+                (Outcome::Win(_), _) => 0.0,
+                _ => 0.5,
+            };
+        }
+        for &mv in played.iter().rev() {
+            game.undo(mv);
+        }
+    }
+
+    // the child of `index` with the best UCT value
+    fn select(&self, index: usize) -> Option<usize> {
+        let log_visits = (self.nodes[index].visits as f64).ln();
+        let uct = |child: usize| {
+            let node = &self.nodes[child];
+            let visits = node.visits as f64;
+            node.points / visits + EXPLORATION * (log_visits / visits).sqrt()
+        };
+        self.nodes[index].children.iter().copied().max_by(|&a, &b| uct(a).total_cmp(&uct(b)))
+    }
+
+    // plays the game out from here and takes the moves back, returning how it
+    // ended
+    fn roll_out<F>(&mut self, game: &mut G, budget: &mut Budget, evaluate: &F) -> Outcome<G::Player>
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        let mut rng = rand::thread_rng();
+        let mut played = Vec::new();
+        let outcome = loop {
+            match game.outcome() {
+                Outcome::InProgress if played.len() < MAX_ROLLOUT as usize => (),
+                outcome => break outcome,
+            }
+            let mut moves = game.legal_moves();
+            if moves.is_empty() {
+                break Outcome::Draw;
+            }
+            self.positions += 1;
+            budget.tick();
+            moves.shuffle(&mut rng);
+            let mv = match self.rollout {
+                Rollout::Heuristic if !rng.gen_bool(ROLLOUT_RANDOMNESS) => Self::heuristic_move(game, &moves, evaluate),
+                _ => moves[0],
+            };
+            game.play(mv);
+            played.push(mv);
+        };
+        for &mv in played.iter().rev() {
+            game.undo(mv);
+        }
+        outcome
+    }
+
+    // a winning move if there is one, otherwise the one `evaluate` likes
+    // best for the side to move
+    fn heuristic_move<F>(game: &mut G, moves: &[G::Move], evaluate: &F) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        let mover = game.side_to_move();
+        let mut best = (i32::MIN, moves[0]);
+        for &mv in moves {
+            game.play(mv);
+            let score = match game.outcome() {
+                Outcome::Win(winner) if winner == mover => i32::MAX,
+                Outcome::Win(_) => i32::MIN,
+                Outcome::Draw => 0,
+                Outcome::InProgress => evaluate(game, mover),
+            };
+            game.undo(mv);
+            if score > best.0 {
+                best = (score, mv);
+            }
+        }
+        best.1
+    }
+
+    // the most tried line from the root and how good its first move looks
+    fn info(&self) -> SearchInfo<G::Move> {
+        let most_tried = |index: usize| self.nodes[index].children.iter().copied().max_by_key(|&child| self.nodes[child].visits);
+        let mut pv = Vec::new();
+        let mut score = 0;
+        let mut index = 0;
+        while let Some(child) = most_tried(index) {
+            let node = &self.nodes[child];
+            if index == 0 {
+                score = ((node.points / node.visits as f64 * 2.0 - 1.0) * SCORE_SCALE).round() as i32;
+            }
+            pv.extend(node.mv);
+            index = child;
+        }
+        SearchInfo { depth: pv.len() as u32, score, nodes: self.positions, pv }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BoardSize, Token};
+    use crate::connect4::{Connect4Board, Connect4Move};
+    use crate::otto::{OttoMove, TootOttoBoard};
+
+    // rollouts that only care about wins and losses
+    fn indifferent<G: Game>(_: &G, _: G::Player) -> i32 {
+        0
+    }
+
+    #[test]
+    fn takes_an_immediate_win() {
+        for rollout in [Rollout::Random, Rollout::Heuristic] {
+            let mut board = Connect4Board::from_notation("343434").unwrap();
+            let mut mcts = Mcts::new(2000, rollout);
+            assert_eq!(mcts.best_move(&mut board, &indifferent), Connect4Move::drop(3));
+            assert_eq!(board.to_notation(), Connect4Board::from_notation("343434").unwrap().to_notation());
+        }
+    }
+
+    #[test]
+    fn spells_otto_when_it_can() {
+        let mut board = TootOttoBoard::from_notation("6/6/6/otT3 O").unwrap();
+        let mut mcts = Mcts::new(2000, Rollout::Random);
+        assert_eq!(mcts.best_move(&mut board, &indifferent), OttoMove::drop(3, Token::O));
+    }
+
+    #[test]
+    fn reports_the_line_it_tried_most() {
+        let mut board = Connect4Board::from_notation("343434").unwrap();
+        let mut mcts = Mcts::new(500, Rollout::Random);
+        let mut report = None;
+        let mv = mcts.search(&mut board, 500, Budget::unlimited(), &indifferent, &mut |info| report = Some(info.clone()));
+        let info = report.unwrap();
+        assert_eq!(info.pv.first(), Some(&mv));
+        assert_eq!(info.depth as usize, info.pv.len());
+        assert!(info.score > SCORE_SCALE as i32 / 2);
+        assert!(info.nodes >= 500);
+    }
+
+    #[test]
+    fn no_iterations_still_finds_a_move() {
+        let mut board = Connect4Board::from_notation("343434").unwrap();
+        let mut mcts = Mcts::new(0, Rollout::Random);
+        let mv = mcts.search(&mut board, 0, Budget::unlimited(), &indifferent, &mut |_| ());
+        assert!(board.legal_moves().contains(&mv));
+        assert!(board.legal_moves().contains(&mcts.best_move(&mut board, &indifferent)));
+    }
+
+    #[test]
+    fn the_tree_is_picked_up_again_a_few_moves_on() {
+        let mut board = Connect4Board::new(BoardSize::Standard);
+        let mut mcts = Mcts::new(3000, Rollout::Random);
+        let mv = mcts.best_move(&mut board, &indifferent);
+        board.play(mv);
+        let reply = mcts.info().pv[1];
+        board.play(reply);
+        mcts.set_root(&board);
+        assert!(mcts.nodes[0].visits > 0);
+        assert_eq!(mcts.nodes[0].key, board.zobrist_key());
+
+        // a position the tree never reached starts it again
+        let elsewhere = Connect4Board::from_notation("0000").unwrap();
+        mcts.set_root(&elsewhere);
+        assert_eq!((mcts.nodes.len(), mcts.nodes[0].visits), (1, 0));
+    }
+}
@@ -0,0 +1,153 @@
+//! Compact text notation for positions on either board
+//!
+//! A position is written like a chess FEN: the rows from the top down
+//! separated by `/`, each row's cells left to right with a run of empty cells
+//! written as its length, then a space and the piece of the side to move.
+//! `7/7/7/7/7/3X3 O` is a standard Connect 4 board after X opens in the
+//! middle. Each board's `from_notation` also reads a game as the moves played
+//! from the start, written as in a game record but run together, like 3342.
+
+use crate::board::{DimensionError, NotationError, MAX_WIDTH};
+
+/// A board's cells row by row from the top, None for an empty cell
+pub type Rows = Vec<Vec<Option<char>>>;
+
+/// Whether `notation` is a position rather than a list of moves
+pub fn is_position(notation: &str) -> bool {
+    notation.contains('/') || notation.contains(' ')
+}
+
+/// Writes `height` rows of `width` cells and `side` after them.
+/// `piece(row, col)` counts rows from the top and is None for an empty cell.
+pub fn write_position(width: u32, height: u32, piece: impl Fn(u32, u32) -> Option<char>, side: char) -> String {
+    let rows: Vec<String> = (0..height).map(|row| {
+        let mut line = String::new();
+        let mut empty = 0;
+        for col in 0..width {
+            match piece(row, col) {
+                Some(c) => {
+                    if empty > 0 {
+                        line += &empty.to_string();
+                        empty = 0;
+                    }
+                    line.push(c);
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            line += &empty.to_string();
+        }
+        line
+    }).collect();
+    format!("{} {}", rows.join("/"), side)
+}
+
+/// Splits a position into its rows and the side to move's piece
+pub fn read_position(notation: &str) -> Result<(Rows, char), NotationError> {
+    let fields: Vec<&str> = notation.split_whitespace().collect();
+    let (rows, side) = match fields[..] {
+        [rows, side] => (rows, side),
+        _ => return Err(NotationError::Unreadable),
+    };
+    let mut side = side.chars();
+    let side = match (side.next(), side.next()) {
+        (Some(side), None) => side,
+        _ => return Err(NotationError::Unreadable),
+    };
+    let rows = rows.split('/').map(read_row).collect::<Result<Rows, NotationError>>()?;
+    if rows.iter().any(|row| row.len() != rows[0].len()) {
+        return Err(NotationError::RaggedRows);
+    }
+    Ok((rows, side))
+}
+
+fn read_row(row: &str) -> Result<Vec<Option<char>>, NotationError> {
+    let mut cells = Vec::new();
+    let mut empty: u32 = 0;
+    for c in row.chars() {
+        match c.to_digit(10) {
+            Some(digit) => {
+                empty = empty * 10 + digit;
+                // no sense reading on, the board can't be this wide
+                if empty > MAX_WIDTH {
+                    return Err(NotationError::Dimensions(DimensionError::TooLarge));
+                }
+            }
+            None => {
+                cells.extend((0..empty).map(|_| None));
+                empty = 0;
+                cells.push(Some(c));
+            }
+        }
+    }
+    cells.extend((0..empty).map(|_| None));
+    Ok(cells)
+}
+
+/// Checks that every piece in `rows` is on the bottom row or another piece
+pub fn check_grounded(rows: &[Vec<Option<char>>]) -> Result<(), NotationError> {
+    for pair in rows.windows(2) {
+        if pair[0].iter().zip(&pair[1]).any(|(above, below)| above.is_some() && below.is_none()) {
+            return Err(NotationError::FloatingPiece);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{DimensionError, MoveError, NotationError};
+    use crate::connect4::Connect4Board;
+    use crate::otto::TootOttoBoard;
+
+    fn connect4_error(notation: &str) -> Option<NotationError> {
+        Connect4Board::from_notation(notation).err()
+    }
+
+    fn otto_error(notation: &str) -> Option<NotationError> {
+        TootOttoBoard::from_notation(notation).err()
+    }
+
+    #[test]
+    fn connect4_positions_read_back_as_written() {
+        let board = Connect4Board::from_notation("3433").unwrap();
+        assert_eq!(board.to_notation(), "7/7/7/3O3/3X3/3XO2 X");
+        let again = Connect4Board::from_notation(&board.to_notation()).unwrap();
+        assert_eq!(again.to_notation(), board.to_notation());
+        assert_eq!(Connect4Board::from_notation("7/7/7/7/7/3X3 O").unwrap().to_notation(), "7/7/7/7/7/3X3 O");
+    }
+
+    #[test]
+    fn otto_positions_read_back_as_written() {
+        let board = TootOttoBoard::from_notation("2T3O").unwrap();
+        let again = TootOttoBoard::from_notation(&board.to_notation()).unwrap();
+        assert_eq!(again.to_notation(), board.to_notation());
+        assert_eq!(again.side_to_move(), board.side_to_move());
+    }
+
+    #[test]
+    fn connect4_rejects_malformed_notation() {
+        assert_eq!(connect4_error("34a"), Some(NotationError::Unreadable));
+        assert_eq!(connect4_error("7/7/7/7/7/3X3"), Some(NotationError::Unreadable));
+        assert_eq!(connect4_error("7/7/7/7/7/3X3 Q"), Some(NotationError::Unreadable));
+        assert_eq!(connect4_error("7/7/7/7/7/6 X"), Some(NotationError::RaggedRows));
+        assert_eq!(connect4_error("3/3/3 X"), Some(NotationError::Dimensions(DimensionError::TooSmall)));
+        assert_eq!(connect4_error("7/7/7/7/3X3/7 O"), Some(NotationError::FloatingPiece));
+        assert_eq!(connect4_error("7/7/7/7/7/2XX3 O"), Some(NotationError::ImpossibleCounts));
+        assert_eq!(connect4_error("7/7/7/7/7/3X3 X"), Some(NotationError::WrongSideToMove));
+        assert_eq!(connect4_error("3333333"), Some(NotationError::IllegalMove(7, MoveError::ColumnFull)));
+    }
+
+    #[test]
+    fn otto_rejects_malformed_notation() {
+        assert_eq!(otto_error("2T3"), Some(NotationError::Unreadable));
+        assert_eq!(otto_error("2X"), Some(NotationError::Unreadable));
+        assert_eq!(otto_error("6/6/6/6"), Some(NotationError::Unreadable));
+        assert_eq!(otto_error("6/6/6/5 T"), Some(NotationError::RaggedRows));
+        assert_eq!(otto_error("6/6/O5/6 T"), Some(NotationError::FloatingPiece));
+        assert_eq!(otto_error("6/6/6/Ot4 T"), None);
+        assert_eq!(otto_error("6/6/6/OO4 T"), Some(NotationError::ImpossibleCounts));
+        assert_eq!(otto_error("6/6/6/O5 O"), Some(NotationError::WrongSideToMove));
+    }
+}
@@ -1,51 +1,135 @@
-use std::{collections:: HashSet, fmt};
+use std::{collections:: HashSet, convert::TryFrom, fmt};
 use super::board::*;
 use std::io::{self, Write};
+use crate::cli::{self, CliGame, Command, Computer};
+use crate::game::{self, Game, Outcome};
+use crate::notation;
 use crate::ottobot;
+use crate::record::GameRecord;
+use crate::search::Bot;
+use crate::zobrist;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
-use web_sys::console;
+
+///A token dropped into a column, or placed on a cell of a board without
+///gravity
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OttoMove {
+    pub col: u32,
+    ///the cell's row when placing, 0 is the top row
+    pub row: Option<u32>,
+    pub token: Token,
+}
+
+impl OttoMove {
+    pub fn drop(col: u32, token: Token) -> OttoMove {
+        OttoMove { col, row: None, token }
+    }
+
+    pub fn place(row: u32, col: u32, token: Token) -> OttoMove {
+        OttoMove { col, row: Some(row), token }
+    }
+
+    ///Reads a move the way game records write it, a column or (row,col)
+    ///followed by the token, like 3T or (2,3)O
+    pub fn parse(text: &str) -> Option<OttoMove> {
+        let token = Token::try_from(text.chars().last()?).ok()?;
+        let place = &text[..text.len() - 1];
+        match place.strip_prefix('(').and_then(|cell| cell.strip_suffix(')')) {
+            Some(cell) => {
+                let (row, col) = cell.split_once(',')?;
+                Some(OttoMove::place(row.parse().ok()?, col.parse().ok()?, token))
+            }
+            None => Some(OttoMove::drop(place.parse().ok()?, token)),
+        }
+    }
+}
+
+///Writes the move the way parse reads it
+impl fmt::Display for OttoMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tok = Cell::from(self.token);
+        match self.row {
+            Some(row) => write!(f, "({},{}){}", row, self.col, tok),
+            None => write!(f, "{}{}", self.col, tok),
+        }
+    }
+}
+
+impl From<&MoveRecord> for OttoMove {
+    fn from(record: &MoveRecord) -> OttoMove {
+        let token = record.token.expect("TOOT and OTTO moves always record their token");
+        match record.kind {
+            MoveKind::Place => OttoMove::place(record.row, record.col, token),
+            _ => OttoMove::drop(record.col, token),
+        }
+    }
+}
 
 ///Player interacts directly with the board
 ///and the board interacts with the bot where the bot will
 ///"return" a move to place on the board
 ///
-/// The board will keep track of the game state and the bot 
-
+///The board keeps track of the game state, the bot only picks moves.
+///
+///Serializes as an OttoState, so loading a board plays its moves again and
+///checks them rather than trusting the cells it's given
 #[wasm_bindgen]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "OttoState", try_from = "OttoState")]
 pub struct TootOttoBoard {
-    board: Vec<Vec<char>>,
+    board: Vec<Vec<Cell>>,
+
+    //who put each token down, which decides whose tiles it came from
+    owners: Vec<Vec<Option<Player>>>,
 
     //for custom size
     width: u32,
     height: u32,
 
     //winning cond checkers
-    last_player: Option<char>, //'o' for otto and 't' for toot
+    last_player: Option<Player>, //Player::One for otto and Player::Two for toot
     last_row: Option<u32>,
     last_col: Option<u32>,
 
-    winner: Option<char>,
+    //whose turn it is, Otto unless set_side_to_move gave Toot the first turn
+    side_to_move: Player,
+
+    //zobrist hash of the tokens on the board
+    hash: u64,
+
+    //tiles each player has left, indexed by Player then Token
+    tiles: [[u32; 2]; 2],
+
+    //whether spelling your own word loses instead of winning
+    misere: bool,
+
+    //false when tokens go on any empty cell instead of dropping
+    gravity: bool,
+
+    //every move on the board, oldest first
+    history: Vec<MoveRecord>,
+    //moves taken back with undo, the next one to redo last
+    redone: Vec<MoveRecord>,
 }
 
 #[wasm_bindgen]
 impl TootOttoBoard {
     #[wasm_bindgen(constructor)]
     pub fn new(size: BoardSize) -> TootOttoBoard {
-        let (width, height) = match size {
-            BoardSize::Standard => (6, 4),
-            BoardSize::Large => (9, 6),
-        };
-
-        TootOttoBoard {
-            width,
-            height,
-            board: vec![vec![' '; width as usize]; height as usize],
-            last_row: None,
-            last_col: None,
-            last_player: None, 
-            winner: None,
+        match size {
+            BoardSize::Standard => TootOttoBoard::sized(6, 4),
+            BoardSize::Large => TootOttoBoard::sized(9, 6),
         }
+    }
 
+    ///An empty board `width` columns wide and `height` rows high, within
+    ///MIN_DIMENSION and MAX_WIDTH/MAX_HEIGHT
+    #[wasm_bindgen]
+    pub fn with_dimensions(width: u32, height: u32) -> Result<TootOttoBoard, DimensionError> {
+        check_dimensions(width, height)?;
+        Ok(TootOttoBoard::sized(width, height))
     }
 
     #[wasm_bindgen]
@@ -68,35 +152,180 @@ impl TootOttoBoard {
         self.last_col
     }
 
+    ///The player whose token goes in next
     #[wasm_bindgen]
-    pub fn get_winner(&self) -> Option<char> {
-        self.winner
+    pub fn side_to_move(&self) -> Player {
+        self.side_to_move
     }
 
-    
-    ///Takes a column and a token and places the token on that column
+    ///Hands the first turn to `player`, for letting Toot start the game.
+    ///Once a token is down the turns follow the moves.
     #[wasm_bindgen]
-    pub fn perform_move_plz(&mut self, col: u32, tok: char, player: char) {
-        //println!("Player: {}, Token: {}, Column: {}", player, tok, col);
-        // decrement from the bottom row to the top row
-        for row in (0..self.height).rev(){
-            if self.board[row as usize][col as usize as usize] == ' ' {
-                self.board[row as usize][col as usize as usize] = tok;
-                self.last_row = Some(row);
-                self.last_col = Some(col);
-                self.last_player = Some(player);
-                break;
+    pub fn set_side_to_move(&mut self, player: Player) -> Result<(), MoveError> {
+        if self.started() {
+            return Err(MoveError::GameAlreadyStarted);
+        }
+        self.side_to_move = player;
+        Ok(())
+    }
+
+    ///Whether the board is played misere
+    #[wasm_bindgen]
+    pub fn misere(&self) -> bool {
+        self.misere
+    }
+
+    ///Turns misere on or off: spelling your own word loses instead of
+    ///winning. Meant to be set before the first move
+    #[wasm_bindgen]
+    pub fn set_misere(&mut self, misere: bool) {
+        self.misere = misere;
+    }
+
+    ///Whether tokens drop to the bottom of their column
+    #[wasm_bindgen]
+    pub fn gravity(&self) -> bool {
+        self.gravity
+    }
+
+    ///Turns gravity off so tokens go on any empty cell, or back on
+    ///Meant to be set before the first move
+    #[wasm_bindgen]
+    pub fn set_gravity(&mut self, gravity: bool) {
+        self.gravity = gravity;
+    }
+
+    ///How many `tok` tiles `player` has left to place
+    #[wasm_bindgen]
+    pub fn tiles_left(&self, player: Player, tok: Token) -> u32 {
+        self.tiles[player as usize][tok as usize]
+    }
+
+    ///Who put down the token at `row` (0 is the top row) and `col`, if
+    ///there is one. None for cells off the board
+    #[wasm_bindgen]
+    pub fn owner(&self, row: u32, col: u32) -> Option<Player> {
+        if row >= self.height || col >= self.width {
+            return None;
+        }
+        self.owners[row as usize][col as usize]
+    }
+
+    ///The position in the notation described in `notation`, with Otto's
+    ///tokens in capitals and Toot's in lower case, then the letter of the
+    ///player to move. Misere and gravity aren't recorded
+    #[wasm_bindgen]
+    pub fn to_notation(&self) -> String {
+        notation::write_position(self.width, self.height, |row, col| {
+            let symbol = self.board[row as usize][col as usize].symbol();
+            self.owner(row, col).map(|player| if player == Player::One { symbol } else { symbol.to_ascii_lowercase() })
+        }, Cell::from(self.side_to_move.letter()).symbol())
+    }
+
+    ///Reads a position written by to_notation, or the moves played from the
+    ///start of a standard game as column and token pairs with Otto first,
+    ///like 2T3O. Positions are read with gravity on
+    #[wasm_bindgen]
+    pub fn from_notation(notation: &str) -> Result<TootOttoBoard, NotationError> {
+        let notation = notation.trim();
+        if !notation::is_position(notation) {
+            let mut board = TootOttoBoard::new(BoardSize::Standard);
+            let moves: Vec<char> = notation.chars().collect();
+            for (number, pair) in (1..).zip(moves.chunks(2)) {
+                let (col, tok) = match pair {
+                    [col, tok] => (col.to_digit(10), Token::try_from(*tok)),
+                    _ => return Err(NotationError::Unreadable),
+                };
+                let (col, tok) = match (col, tok) {
+                    (Some(col), Ok(tok)) => (col, tok),
+                    _ => return Err(NotationError::Unreadable),
+                };
+                board.try_play(col, tok, board.side_to_move).map_err(|error| NotationError::IllegalMove(number, error))?;
             }
+            return Ok(board);
+        }
+
+        TootOttoBoard::read_position(notation, true)
+    }
+
+    ///The board as a JavaScript object, see OttoState
+    #[wasm_bindgen]
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(self)?)
+    }
+
+    ///Reads a board written by to_js
+    #[wasm_bindgen]
+    pub fn from_js(value: JsValue) -> Result<TootOttoBoard, JsValue> {
+        Ok(serde_wasm_bindgen::from_value(value)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn status(&self) -> GameStatus {
+        self.outcome().into()
+    }
+
+    ///The player who spelled their word, if only one of them has
+    #[wasm_bindgen]
+    pub fn winner(&self) -> Option<Player> {
+        match self.outcome() {
+            Outcome::Win(player) => Some(player),
+            _ => None,
         }
     }
 
+    
+    ///Takes a column and a token and places the side to move's token on that
+    ///column, unless it's full or they have no `tok` tiles left
+    ///Moves taken back with undo can't be redone afterwards
     #[wasm_bindgen]
-    pub fn is_terminal(&mut self) -> bool {
-        console::log_1(&"is_terminal".into());
-        if (self.has_winner() !='f') || self.is_draw() {
-            return true;
+    pub fn perform_move_plz(&mut self, col: u32, tok: Token) {
+        if self.allows_move(col) && self.tiles_left(self.side_to_move, tok) > 0 {
+            self.redone.clear();
+            self.place(col, tok);
         }
-        return false;
+    }
+
+    ///Puts the side to move's `tok` on the empty cell at `row` (0 is the top
+    ///row) and `col` of a board without gravity, unless that isn't allowed
+    ///or they have no `tok` tiles left
+    ///Moves taken back with undo can't be redone afterwards
+    #[wasm_bindgen]
+    pub fn perform_place(&mut self, row: u32, col: u32, tok: Token) {
+        if self.allows_place(row, col) && self.tiles_left(self.side_to_move, tok) > 0 {
+            self.redone.clear();
+            self.place_at(row, col, tok);
+        }
+    }
+
+    ///Takes back the last move, which redo can play again
+    #[wasm_bindgen]
+    pub fn undo(&mut self) -> Option<MoveRecord> {
+        let record = self.take_back()?;
+        self.redone.push(record);
+        Some(record)
+    }
+
+    ///Plays the last move taken back with undo again
+    #[wasm_bindgen]
+    pub fn redo(&mut self) -> Option<MoveRecord> {
+        let record = self.redone.pop()?;
+        match record.kind {
+            MoveKind::Place => self.place_at(record.row, record.col, record.token.unwrap()),
+            _ => self.place(record.col, record.token.unwrap()),
+        }
+        Some(record)
+    }
+
+    ///Every move played so far, oldest first
+    #[wasm_bindgen]
+    pub fn history(&self) -> Vec<MoveRecord> {
+        self.history.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_terminal(&self) -> bool {
+        self.outcome() != Outcome::InProgress
     }
 
     #[wasm_bindgen]
@@ -104,22 +333,114 @@ impl TootOttoBoard {
         self.available_moves().is_empty()
     }
 
-    
-    ///Takes a board and checks if the board is in a winning position
-    ///returns "w" if there is a winner
-    /// "t" if the game is a tie
-    /// "f" if the game is still in progress
+    ///Places `tok` like perform_move_plz, but checks the move first and
+    ///leaves the board alone if it's illegal or it isn't `player`'s turn
+    #[wasm_bindgen]
+    pub fn try_play(&mut self, col: u32, tok: Token, player: Player) -> Result<MoveOutcome, MoveError> {
+        self.check_move(col, tok, player)?;
+        self.perform_move_plz(col, tok);
+        Ok(MoveOutcome { row: self.last_row.unwrap(), col, status: self.status() })
+    }
+
+    ///Places `tok` like perform_place, but checks the move first and leaves
+    ///the board alone if it's illegal or it isn't `player`'s turn
     #[wasm_bindgen]
-    pub fn has_winner(&mut self) -> char {
+    pub fn try_place(&mut self, row: u32, col: u32, tok: Token, player: Player) -> Result<MoveOutcome, MoveError> {
+        self.check_place(row, col, tok, player)?;
+        self.perform_place(row, col, tok);
+        Ok(MoveOutcome { row, col, status: self.status() })
+    }
+
+    ///Takes a column and reutrns true if a move can be made into
+    ///that column. False otherwise
+    #[wasm_bindgen]
+    pub fn allows_move(&self, col: u32) -> bool{
+        self.gravity && col < self.width() && self.board[0][col as usize] == Cell::Empty
+    }
 
-        let row = self.last_row;
-        let col = self.last_col;
+    ///Takes a row (0 is the top row) and a column and returns true if a
+    ///token can be placed there on a board without gravity
+    #[wasm_bindgen]
+    pub fn allows_place(&self, row: u32, col: u32) -> bool {
+        !self.gravity && row < self.height && col < self.width
+            && self.board[row as usize][col as usize] == Cell::Empty
+    }
+}
 
-        //no move made so far
-        if row.is_none() || col.is_none(){
-            return 'f';
+impl TootOttoBoard {
+    //reads a position written by to_notation, where tokens may float if
+    //`grounded` is false
+    fn read_position(notation: &str, grounded: bool) -> Result<TootOttoBoard, NotationError> {
+        let (rows, side) = notation::read_position(notation)?;
+        let side = match side {
+            'O' => Player::One,
+            'T' => Player::Two,
+            _ => return Err(NotationError::Unreadable),
+        };
+        let mut board = TootOttoBoard::with_dimensions(rows[0].len() as u32, rows.len() as u32)?;
+        if grounded {
+            notation::check_grounded(&rows)?;
         }
 
+        let mut placed = [0; 2];
+        for (row, cells) in (0..).zip(&rows) {
+            for (col, &piece) in (0..).zip(cells) {
+                let symbol = match piece {
+                    Some(symbol) => symbol,
+                    None => continue,
+                };
+                let player = if symbol.is_ascii_uppercase() { Player::One } else { Player::Two };
+                let tok = Token::try_from(symbol).map_err(|_| NotationError::Unreadable)?;
+                if board.tiles_left(player, tok) == 0 {
+                    return Err(NotationError::ImpossibleCounts);
+                }
+                board.set_cell(row, col, player, tok);
+                placed[player as usize] += 1;
+            }
+        }
+
+        //either player may have started, but after that they took turns
+        let (otto, toot) = (placed[0], placed[1]);
+        if otto > toot + 1 || toot > otto + 1 {
+            return Err(NotationError::ImpossibleCounts);
+        }
+        if (otto > toot && side == Player::One) || (toot > otto && side == Player::Two) {
+            return Err(NotationError::WrongSideToMove);
+        }
+        board.side_to_move = side;
+        Ok(board)
+    }
+
+    //whether any token is down or any move has been played
+    fn started(&self) -> bool {
+        !self.history.is_empty() || self.board.iter().flatten().any(|&cell| cell != Cell::Empty)
+    }
+
+    fn sized(width: u32, height: u32) -> TootOttoBoard {
+        //six of each letter per player on the standard board, enough
+        //between them to fill the board at any size
+        let per_letter = (width * height).div_ceil(4);
+
+        TootOttoBoard {
+            width,
+            height,
+            board: vec![vec![Cell::Empty; width as usize]; height as usize],
+            owners: vec![vec![None; width as usize]; height as usize],
+            last_row: None,
+            last_col: None,
+            last_player: None, 
+            side_to_move: Player::One,
+            hash: 0,
+            tiles: [[per_letter; 2]; 2],
+            misere: false,
+            gravity: true,
+            history: Vec::new(),
+            redone: Vec::new(),
+        }
+    }
+
+    ///Returns the set of players whose word is spelled on the board
+    fn winners(&self) -> HashSet<Player> {
         //beware that either player could win so we need to check both
 
         //check for horizontal win
@@ -133,14 +454,14 @@ impl TootOttoBoard {
             for col in 0..cols {
                 let token = self.board[row as usize][col as usize];
 
-                if token == ' '{
+                if token == Cell::Empty {
                     continue;
                 }
 
                 //match to opposite string instead
-                let opposite: char = match &token {
-                    'O' => 'T',
-                    'T' => 'O',
+                let (opposite, player) = match token {
+                    Cell::O => (Cell::T, Player::One),
+                    Cell::T => (Cell::O, Player::Two),
                     _ => continue, //skip if empty
                 };
 
@@ -152,7 +473,7 @@ impl TootOttoBoard {
                     && (self.board[row as usize][col as usize + 2] == opposite)
                     && (self.board[row as usize][col as usize + 3] == token)
                 {
-                    winners_set.insert(token);
+                    winners_set.insert(player);
                     continue;
                 }
 
@@ -162,7 +483,7 @@ impl TootOttoBoard {
                         &&(self.board[row as usize -2][col as usize] == opposite)
                         &&(self.board[row as usize -3][col as usize] == token)
                     {
-                        winners_set.insert(token);
+                        winners_set.insert(player);
                         continue;
                     }
 
@@ -172,7 +493,7 @@ impl TootOttoBoard {
                         && (self.board[row as usize -2][col as usize+2] == opposite)
                         && (self.board[row as usize -3][col as usize+3] == token)
                     {
-                        winners_set.insert(token);
+                        winners_set.insert(player);
                         continue;
                     }
 
@@ -182,93 +503,231 @@ impl TootOttoBoard {
                         && (self.board[row as usize -2][col as usize-2] == opposite)
                         && (self.board[row as usize -3][col as usize-3] == token)
                     {
-                        winners_set.insert(token);
+                        winners_set.insert(player);
                         continue;
                     }
                 }
             }
         }
+        winners_set
+    }
 
-        match winners_set.len() {
-            0 => 'f',
-            1 => {
-                //get the char from the winners set
-                let winner = winners_set.iter().next().unwrap();
-                
-                //set board.winner to the winner
-                self.winner = Some(*winner);
-                return 'w';
-            },
-            _ => 't',
+    ///Returns why `player` can't play `tok` in `col` right now, if they can't
+    pub fn check_move(&self, col: u32, tok: Token, player: Player) -> Result<(), MoveError> {
+        if self.is_terminal() {
+            Err(MoveError::GameAlreadyOver)
+        } else if player != self.side_to_move {
+            Err(MoveError::WrongPlayerToMove)
+        } else if col >= self.width {
+            Err(MoveError::ColumnOutOfRange)
+        } else if !self.gravity {
+            Err(MoveError::DropNotAllowed)
+        } else if !self.allows_move(col) {
+            Err(MoveError::ColumnFull)
+        } else if self.tiles_left(player, tok) == 0 {
+            Err(MoveError::OutOfTokens)
+        } else {
+            Ok(())
         }
     }
 
-    ///Takes a column and reutrns true if a move can be made into
-    ///that column. False otherwise
-    #[wasm_bindgen]
-    pub fn allows_move(&self, col: u32) -> bool{
-        col < self.width() as u32 && self.board[0][col as usize] == ' '
+    ///Plays `mv` for `player` like try_play or try_place would
+    pub fn try_move(&mut self, mv: OttoMove, player: Player) -> Result<MoveOutcome, MoveError> {
+        match mv.row {
+            Some(row) => self.try_place(row, mv.col, mv.token, player),
+            None => self.try_play(mv.col, mv.token, player),
+        }
     }
-}
 
-impl TootOttoBoard {
+    ///Rebuilds a recorded game: the rules and start position come from the
+    ///record's tags, then every move is checked as it's played again
+    pub fn from_record(record: &GameRecord) -> Result<TootOttoBoard, NotationError> {
+        let rules: Vec<&str> = record.tag("Rules").unwrap_or("Standard").split_whitespace().collect();
+        let history = record.moves().iter()
+            .map(|text| OttoMove::parse(text).ok_or(NotationError::Unreadable))
+            .collect::<Result<_, _>>()?;
+        TootOttoBoard::try_from(OttoState {
+            misere: rules.contains(&"misere"),
+            gravity: !rules.contains(&"no-gravity"),
+            start: record.tag("Start").unwrap_or("").to_string(),
+            history,
+            redone: Vec::new(),
+        })
+    }
 
-    /// Returns a list(HashSet) of available moves on the board
-    pub fn available_moves(&self) -> Vec<u32>{
+    ///The board as JSON, see OttoState
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a board always serializes")
+    }
+
+    ///Reads a board written by to_json
+    pub fn from_json(json: &str) -> serde_json::Result<TootOttoBoard> {
+        serde_json::from_str(json)
+    }
+
+    ///Returns why `player` can't place `tok` on the cell at `row` and `col`
+    ///right now, if they can't
+    pub fn check_place(&self, row: u32, col: u32, tok: Token, player: Player) -> Result<(), MoveError> {
+        if self.is_terminal() {
+            Err(MoveError::GameAlreadyOver)
+        } else if player != self.side_to_move {
+            Err(MoveError::WrongPlayerToMove)
+        } else if self.gravity {
+            Err(MoveError::PlacementNotAllowed)
+        } else if row >= self.height || col >= self.width {
+            Err(MoveError::CellOutOfRange)
+        } else if !self.allows_place(row, col) {
+            Err(MoveError::CellTaken)
+        } else if self.tiles_left(player, tok) == 0 {
+            Err(MoveError::OutOfTokens)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns every move the side to move has tiles for: a drop into each
+    /// column with room, or without gravity a placement on each empty cell
+    pub fn available_moves(&self) -> Vec<OttoMove> {
         let mut moves = Vec::new();
-        for col in 0..self.width {
-            if self.allows_move(col) {
-                moves.push(col);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                for token in [Token::O, Token::T] {
+                    let mv = if !self.gravity && self.allows_place(row, col) {
+                        OttoMove::place(row, col, token)
+                    } else if row == 0 && self.allows_move(col) {
+                        OttoMove::drop(col, token)
+                    } else {
+                        continue;
+                    };
+                    if self.tiles_left(self.side_to_move, token) > 0 {
+                        moves.push(mv);
+                    }
+                }
             }
         }
-        return moves;
+        moves
     }
 
+    fn piece_index(player: Player, tok: Token) -> usize {
+        player as usize * 2 + tok as usize
+    }
 
-    ///Takes a row and column and returns the token at that position
-    pub fn get(&self, row: usize, col: usize) -> char {
-        self.board[row][col]
+    fn cell_index(&self, row: u32, col: u32) -> usize {
+        (row * self.width + col) as usize
     }
 
-    ///Takes a column and removes the token from that column
-    /// this is used to undo a move (for AI)
-    pub fn undo_move(&mut self, col: usize) {
-        for row in 0..self.height {
-            if self.board[row as usize][col as usize] != ' ' {
-                self.board[row as usize][col as usize] = ' ';
+    ///Takes a row and column and returns the token at that position,
+    ///empty off the board
+    pub fn get(&self, row: usize, col: usize) -> Cell {
+        self.board.get(row).and_then(|cells| cells.get(col)).copied().unwrap_or(Cell::Empty)
+    }
+
+    ///Places the side to move's token, records it and passes the turn on.
+    ///The search plays its moves through here so it doesn't forget what
+    ///undo took back
+    fn place(&mut self, col: u32, tok: Token) {
+        // decrement from the bottom row to the top row
+        for row in (0..self.height).rev(){
+            if self.board[row as usize][col as usize] == Cell::Empty {
+                self.put(row, col, tok, MoveKind::Drop);
                 break;
             }
         }
     }
 
+    ///Places the side to move's token on the cell at `row` and `col` of a
+    ///board without gravity, like place does
+    fn place_at(&mut self, row: u32, col: u32, tok: Token) {
+        self.put(row, col, tok, MoveKind::Place);
+    }
 
+    fn put(&mut self, row: u32, col: u32, tok: Token, kind: MoveKind) {
+        let player = self.side_to_move;
+        self.history.push(MoveRecord {
+            col,
+            row,
+            token: Some(tok),
+            kind,
+            player,
+            prior_row: self.last_row,
+            prior_col: self.last_col,
+            prior_player: self.last_player,
+        });
+        self.set_cell(row, col, player, tok);
+        self.last_row = Some(row);
+        self.last_col = Some(col);
+        self.last_player = Some(player);
+        self.side_to_move = player.other();
+    }
 
+    ///Puts `player`'s `tok` on the cell at `row` and `col` without
+    ///recording a move
+    fn set_cell(&mut self, row: u32, col: u32, player: Player, tok: Token) {
+        self.board[row as usize][col as usize] = tok.into();
+        self.owners[row as usize][col as usize] = Some(player);
+        self.hash ^= zobrist::piece_key(self.cell_index(row, col), Self::piece_index(player, tok));
+        self.tiles[player as usize][tok as usize] -= 1;
+    }
 
-    ///Returns the value of the game
-    pub fn game_value(&mut self) -> i32 {
-        if self.has_winner() == 'w' {
-            match self.winner {
-                Some('O') => i32::MAX,
-                Some('T') => i32::MIN,
-                _ => 0,
-            }
-        } else {
-            0
-        }
+    ///Removes the last token placed and restores the last_* fields from before it
+    fn take_back(&mut self) -> Option<MoveRecord> {
+        let record = self.history.pop()?;
+        let (row, col) = (record.row, record.col);
+        let tok = record.token.unwrap();
+        self.hash ^= zobrist::piece_key(self.cell_index(row, col), Self::piece_index(record.player, tok));
+        self.tiles[record.player as usize][tok as usize] += 1;
+        self.board[row as usize][col as usize] = Cell::Empty;
+        self.owners[row as usize][col as usize] = None;
+        self.last_row = record.prior_row;
+        self.last_col = record.prior_col;
+        self.last_player = record.prior_player;
+        self.side_to_move = record.player;
+        Some(record)
     }
 
 
+
+
     ///this will return a uszie representing the column the player wants to place
     ///Dont forget error handling
-    /// allows_move() will be used to check if the move is valid
-    pub fn get_player_move(&self) -> (u32, char) {
+    /// check_move() will be used to check if the move is valid
+    /// u and r ask to undo and redo instead, n prints the position's notation
+    /// without gravity the row comes first, "row column token"
+    pub fn get_player_move(&self) -> Command<OttoMove> {
+        let player = self.side_to_move;
         loop {
-            print!("Player's choice (enter as \"column token\"): ");
+            if self.gravity {
+                print!("Player's choice (enter as \"column token\"): ");
+            } else {
+                print!("Player's choice (enter as \"row column token\"): ");
+            }
             io::stdout().flush().unwrap();
             let mut player_move = String::new();
             io::stdin().read_line(&mut player_move).unwrap();
             
-            let player_move: Vec<&str> = player_move.trim().split_whitespace().collect();
+            let mut player_move: Vec<&str> = player_move.split_whitespace().collect();
+
+            match player_move[..] {
+                ["u"] => return Command::Undo,
+                ["r"] => return Command::Redo,
+                ["n"] => {
+                    println!("{}", self.to_notation());
+                    continue;
+                }
+                _ => (),
+            }
+
+            let row = if self.gravity || player_move.is_empty() {
+                None
+            } else {
+                match player_move.remove(0).parse::<u32>() {
+                    Ok(row) => Some(row),
+                    Err(_) => {
+                        println!("Please enter a valid row number");
+                        continue;
+                    }
+                }
+            };
             
             if player_move.len() != 2 {
                 println!("Please enter a column and a token");
@@ -283,165 +742,289 @@ impl TootOttoBoard {
                 }
             };
 
-            let mut tok: char = match player_move[1].parse() {
-                Ok(tok) => tok,
-                Err(_) => {
+            let token = match player_move[1].parse::<char>().map(Token::try_from) {
+                Ok(Ok(token)) => token,
+                _ => {
                     println!("Please enter a valid token");
                     continue;
                 }
             };
 
-    
-            if tok == 'o' {
-                tok = 'O';
-            } else if tok == 't' {
-                tok = 'T';
-            }
-
-            if !(tok == 'O' || tok == 'T') {
-                println!("Please enter a valid token");
-                continue;
-            }
-
-            if !self.allows_move(col) {
-                println!("Column is full, please choose another column");
+            let (mv, checked) = match row {
+                Some(row) => (OttoMove::place(row, col, token), self.check_place(row, col, token, player)),
+                None => (OttoMove::drop(col, token), self.check_move(col, token, player)),
+            };
+            if let Err(error) = checked {
+                println!("Can't play there: {}", error);
                 continue;
             }
 
-            return (col, tok);
+            return Command::Play(mv);
 
         }
     }
 
    //host the game on cli
-    pub fn host_game(&mut self) -> String {
+    pub fn host_game(&mut self) -> GameRecord {
         println!("Welcome to Toot and Otto!\n");
-        let mut game_over = false;
-        let mut moves = String::new();
-        let mut turn = 'O';
-        while !game_over {
-            println!("{}", self);
-
-            if turn == 'O' {
-                println!("Otto's turn");
-            } else {
-                println!("Toot's turn");
-            }
-
-            let (player_move_col, player_move_token) = self.get_player_move();
-            self.perform_move_plz(player_move_col as u32, player_move_token, turn);
-            moves.push_str(&player_move_col.to_string());
-
-            //check if the game is over
-            let winner = self.has_winner();
-            if winner == 'w' {
-                println!("{}", self);
-
-                match self.winner {
-                    Some('O') => println!("Otto wins -- Congratulations!"),
-                    Some('T') => println!("Toot wins -- Congratulations!"),
-                    _ => (),
-                }
-                game_over = true;
-            } else if self.is_draw() {
-                println!("{}", self);
-                println!("It's a draw!");
-                game_over = true;
-            } else if winner == 't' {
-                println!("{}", self);
-                println!("Tie Game!");
-                game_over = true;
-            }
-            turn = if turn == 'O' { 'T' } else { 'O' };
-        }
-        moves
+        cli::host_game(self, Vec::new())
     }
 
-    //get the ai move for a state of the current board
-    pub fn get_ai_move(&mut self, ai_tok:char, difficulty_var:u32) -> (u32, char){
+    //get the ai move for the side to move on the current board
+    pub fn get_ai_move(&mut self, difficulty_var:u32) -> OttoMove {
 
         let difficulty = match difficulty_var {
             1 => Difficulty::Easy,
             2 => Difficulty::Hard,
+            3 => Difficulty::Mcts,
             _ => Difficulty::Easy,
         };
 
-        let ai = ottobot::OttoBot::new(difficulty, ai_tok);
-        let ai_move_string = ai.best_move(self, ai_tok);
-        let ai_move: Vec<char> = ai_move_string.chars().collect();
-        let ai_move_char = ai_move[1];
-        let ai_move = ai_move[0].to_digit(10).unwrap();
-        (ai_move as u32, ai_move_char)
+        let mut ai = ottobot::OttoBot::new(difficulty, self.side_to_move);
+        ai.choose_move(self)
     }
 
 
 
-    pub fn host_game_AI(&mut self, difficulty: Difficulty, tok: char) -> String {
+    ///`player` is the person's side, and they go first on an empty board
+    #[allow(non_snake_case)]
+    pub fn host_game_AI(&mut self, difficulty: Difficulty, player: Player, think_millis: Option<u32>) -> GameRecord {
         println!("Welcome to Toot and Otto!\n");
-        let mut game_over = false;
-        let mut moves = String::new();
-        let mut ai_tok = if tok == 'T' { 'O' } else { 'T' };
-        let ai = ottobot::OttoBot::new(difficulty, ai_tok);
-        let mut turn = tok;
-        while !game_over {
-            println!("{}", self);
-            
-            let turn_name = if turn == 'O' { "Otto" } else { "Toot" };
-            println!("{}'s turn", turn_name);
-
-            if turn == ai_tok {
-                println!("AI is thinking...");
-                let ai_move_string = ai.best_move(self, ai_tok);
-                let ai_move: Vec<char> = ai_move_string.chars().collect();
-                let ai_move_char = ai_move[1];
-                let ai_move = ai_move[0].to_digit(10).unwrap();
-                self.perform_move_plz(ai_move as u32, ai_move_char, ai_tok);
-                moves.push_str(&ai_move.to_string());
-            } else {
-                let (player_move_col, player_move_token) = self.get_player_move();
-                self.perform_move_plz(player_move_col as u32, player_move_token, turn);
-                moves.push_str(&player_move_col.to_string());
+        let ai_player = player.other();
+        let mut ai = ottobot::OttoBot::new(difficulty, ai_player);
+        //a game that's under way carries on with whoever's turn it is
+        if !self.started() {
+            self.set_side_to_move(player).expect("no token is down yet");
+        }
+        cli::host_game(self, vec![Computer { plays: ai_player, bot: &mut ai, think_millis }])
+    }
+}
+
+///A board the way serde writes it: the rules, the position before the first
+///move in notation, and the moves since. Tiles left, owners and the hash all
+///follow from those, so they're worked out again by playing the moves when a
+///board is loaded, and a move that isn't legal fails the load.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OttoState {
+    pub misere: bool,
+    pub gravity: bool,
+    pub start: String,
+    pub history: Vec<OttoMove>,
+    ///moves taken back with undo, the next one to redo last
+    pub redone: Vec<OttoMove>,
+}
+
+impl From<TootOttoBoard> for OttoState {
+    fn from(mut board: TootOttoBoard) -> OttoState {
+        let history = board.history.iter().map(OttoMove::from).collect();
+        let redone = board.redone.iter().map(OttoMove::from).collect();
+        while board.undo().is_some() {}
+        OttoState {
+            misere: board.misere,
+            gravity: board.gravity,
+            start: board.to_notation(),
+            history,
+            redone,
+        }
+    }
+}
+
+impl TryFrom<OttoState> for TootOttoBoard {
+    type Error = NotationError;
+
+    fn try_from(state: OttoState) -> Result<TootOttoBoard, NotationError> {
+        let mut board = TootOttoBoard::read_position(&state.start, state.gravity)?;
+        board.set_gravity(state.gravity);
+        board.set_misere(state.misere);
+        //moves that were taken back get played and checked too, then taken
+        //back again
+        let moves = state.history.iter().chain(state.redone.iter().rev());
+        for (number, &mv) in (1..).zip(moves) {
+            board.try_move(mv, board.side_to_move).map_err(|error| NotationError::IllegalMove(number, error))?;
+        }
+        for _ in &state.redone {
+            board.undo();
+        }
+        Ok(board)
+    }
+}
+
+impl Game for TootOttoBoard {
+    type Move = OttoMove;
+    type Player = Player;
+
+    fn side_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    fn legal_moves(&self) -> Vec<OttoMove> {
+        self.available_moves()
+    }
+
+    fn play(&mut self, mv: OttoMove) {
+        match mv.row {
+            Some(row) => self.place_at(row, mv.col, mv.token),
+            None => self.place(mv.col, mv.token),
+        }
+    }
+
+    fn undo(&mut self, _mv: OttoMove) {
+        self.take_back();
+    }
+
+    fn outcome(&self) -> GameOutcome {
+        let winners = self.winners();
+        match winners.len() {
+            0 if self.is_draw() => Outcome::Draw,
+            0 => Outcome::InProgress,
+            1 => {
+                let speller = *winners.iter().next().unwrap();
+                Outcome::Win(if self.misere { speller.other() } else { speller })
             }
+            _ => Outcome::Draw, //both words spelled at once is a tie
+        }
+    }
 
-            //check if the game is over
-            let winner = self.has_winner();
-            if winner == 'w' {
-                println!("{}", self);
+    ///Zobrist hash of the tokens on the board, see `zobrist`
+    fn zobrist_key(&self) -> u64 {
+        self.hash
+    }
 
-                match self.winner {
-                    Some('O') => println!("Otto wins -- Congratulations!"),
-                    Some('T') => println!("Toot wins -- Congratulations!"),
-                    _ => (),
-                }
-                game_over = true;
-            } else if self.is_draw() {
-                println!("{}", self);
-                println!("It's a draw!");
-                game_over = true;
-            } else if winner == 't' {
-                println!("{}", self);
-                println!("Tie Game!");
-                game_over = true;
+    fn side_key(&self) -> u64 {
+        zobrist::side_key(self.side_to_move as usize)
+    }
+}
+
+impl CliGame for TootOttoBoard {
+    fn read_move(&self) -> Command<OttoMove> {
+        println!("{}'s turn", self.side_to_move.word());
+        self.get_player_move()
+    }
+
+    fn objective(&self) -> String {
+        if self.misere {
+            "Misère rules: whoever spells their own word first loses!".to_string()
+        } else {
+            "Otto wants to spell OTTO and Toot wants to spell TOOT.".to_string()
+        }
+    }
+
+    fn print_congrats(&self) {
+        println!("{}", self);
+        match self.outcome() {
+            Outcome::Win(player) if self.misere => {
+                println!("{} spelled their word, so {} wins -- Congratulations!", player.other().word(), player.word())
             }
+            Outcome::Win(player) => println!("{} wins -- Congratulations!", player.word()),
+            Outcome::Draw if self.winners().len() > 1 => println!("Tie Game!"),
+            Outcome::Draw => println!("It's a draw!"),
+            Outcome::InProgress => (),
+        }
+    }
 
-            turn = if turn == 'O' { 'T' } else { 'O' };
+    fn commit(&mut self, mv: OttoMove) {
+        match mv.row {
+            Some(row) => self.perform_place(row, mv.col, mv.token),
+            None => self.perform_move_plz(mv.col, mv.token),
         }
-        moves
-    }   
+    }
+
+    fn take_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    fn replay(&mut self) -> bool {
+        self.redo().is_some()
+    }
+
+    fn moves(&self) -> Vec<String> {
+        self.history.iter().map(|record| OttoMove::from(record).to_string()).collect()
+    }
+
+    fn record_tags(&self) -> Vec<(&'static str, String)> {
+        let mut rules = "Standard".to_string();
+        if self.misere {
+            rules += " misere";
+        }
+        if !self.gravity {
+            rules += " no-gravity";
+        }
+        vec![
+            ("Game", "TOOT and OTTO".to_string()),
+            ("Board", format!("{}x{}", self.width, self.height)),
+            ("Rules", rules),
+            ("Start", self.to_notation()),
+        ]
+    }
+
+    fn seats(&self) -> Vec<Player> {
+        vec![Player::One, Player::Two]
+    }
+
+    fn name(&self, player: Player) -> String {
+        player.word().to_string()
+    }
 }
 
 impl fmt::Display for TootOttoBoard {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for row in &self.board {
-            for &cell in row {
-                write!(f, "{} ", cell)?;
-            }
-            writeln!(f)?;
-        }
-        for col in 0..self.width {
-            write!(f, "{} ", col)?;
-        }
-        writeln!(f)?;
-        Ok(())
+        game::write_grid(f, self.width, self.height, !self.gravity, |row, col| self.get(row as usize, col as usize).symbol())?;
+        let left = |player: Player| format!("{}: {} T, {} O left", player.word(), self.tiles_left(player, Token::T), self.tiles_left(player, Token::O));
+        writeln!(f, "{}   {}", left(Player::One), left(Player::Two))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toot_can_only_go_first_before_any_token_is_down() {
+        let mut board = TootOttoBoard::new(BoardSize::Standard);
+        board.set_side_to_move(Player::Two).unwrap();
+        board.try_play(2, Token::T, Player::Two).unwrap();
+        assert_eq!(board.side_to_move(), Player::One);
+        assert_eq!(board.set_side_to_move(Player::Two), Err(MoveError::GameAlreadyStarted));
+        assert_eq!(board.side_to_move(), Player::One);
+        assert_eq!(board.try_play(3, Token::O, Player::Two).unwrap_err(), MoveError::WrongPlayerToMove);
+    }
+
+    #[test]
+    fn cells_off_the_board_have_no_owner() {
+        let mut board = TootOttoBoard::new(BoardSize::Standard);
+        board.try_play(5, Token::T, Player::One).unwrap();
+        assert_eq!(board.owner(3, 5), Some(Player::One));
+        assert_eq!(board.owner(4, 5), None);
+        assert_eq!(board.owner(3, 6), None);
+        assert_eq!(board.owner(u32::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn cells_off_the_board_are_empty() {
+        let mut board = TootOttoBoard::new(BoardSize::Standard);
+        board.try_play(5, Token::T, Player::One).unwrap();
+        assert_eq!(board.get(3, 5), Cell::T);
+        assert_eq!(board.get(4, 5), Cell::Empty);
+        assert_eq!(board.get(3, 6), Cell::Empty);
+        assert_eq!(board.get(usize::MAX, usize::MAX), Cell::Empty);
+    }
+
+    #[test]
+    fn boards_come_back_from_json_as_they_were() {
+        let mut board = TootOttoBoard::new(BoardSize::Standard);
+        board.set_misere(true);
+        board.set_side_to_move(Player::Two).unwrap();
+        board.try_play(2, Token::T, Player::Two).unwrap();
+        board.try_play(2, Token::O, Player::One).unwrap();
+        board.try_play(4, Token::O, Player::Two).unwrap();
+        board.undo();
+
+        let json = board.to_json();
+        let loaded = TootOttoBoard::from_json(&json).unwrap();
+        assert_eq!(loaded.to_notation(), board.to_notation());
+        assert_eq!(loaded.tiles_left(Player::Two, Token::T), board.tiles_left(Player::Two, Token::T));
+        assert_eq!(Game::zobrist_key(&loaded), Game::zobrist_key(&board));
+        assert_eq!(OttoState::from(loaded.clone()), OttoState::from(board));
+        assert_eq!(loaded.to_json(), json);
+    }
+}
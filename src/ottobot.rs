@@ -1,57 +1,194 @@
+use crate::board::{Cell, Difficulty, Player};
+use crate::budget::Budget;
+use crate::game;
+use crate::mcts::{Mcts, Rollout, DEFAULT_ITERATIONS};
+use crate::otto::{OttoMove, TootOttoBoard};
+use crate::search::{Bot, BotConfig, SearchInfo, Searcher};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp;
-
-use crate::board::Difficulty;
-use crate::otto::TootOttoBoard;
 use wasm_bindgen::prelude::*;
-use web_sys::console;
+
+//slots in the bot's transposition table
+const TABLE_SIZE: usize = 1 << 18;
+
+//deepest the bot searches without gravity, where every empty cell is a move
+const FREE_PLACEMENT_DEPTH: u32 = 3;
+
 #[wasm_bindgen]
 pub struct OttoBot {
+    difficulty: Difficulty,
     depth: u32,
-    play_as: char,
+    searcher: Searcher<TootOttoBoard>,
+    //only set for Difficulty::Mcts, which plays with it instead of searching
+    mcts: Option<Mcts<TootOttoBoard>>,
+    weights: OttoWeights,
+}
+
+///What the bot's heuristic makes of each pattern it looks for, so versions
+///of it can be played against each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OttoWeights {
+    ///each piece in the middle column or columns
+    pub center: i32,
+    ///the player's whole word
+    pub word: i32,
+    ///the first three letters of it
+    pub three: i32,
+    ///the first two letters of it, not along rows
+    pub two: i32,
+    ///one of the opponent's letters capping three of the player's
+    pub block: i32,
+}
+
+impl Default for OttoWeights {
+    fn default() -> OttoWeights {
+        OttoWeights { center: 1, word: 100000, three: 100, two: 10, block: 500 }
+    }
 }
 
 #[wasm_bindgen]
 impl OttoBot {
+    ///`ai_player` is kept for the JS API, the bot plays whichever side the
+    ///board says is to move
     #[wasm_bindgen(constructor)]
-    pub fn new(difficulty: Difficulty, ai_token: char) -> OttoBot {
+    pub fn new(difficulty: Difficulty, _ai_player: Player) -> OttoBot {
         let depth = match difficulty {
             Difficulty::Easy => 3,
-            Difficulty::Hard => 5,
+            // there's no solver for TOOT and OTTO, play as well as Hard does
+            Difficulty::Hard | Difficulty::Perfect | Difficulty::Mcts => 5,
+        };
+        let mcts = match difficulty {
+            Difficulty::Mcts => Some(Mcts::new(DEFAULT_ITERATIONS, Rollout::Random)),
+            _ => None,
         };
-        let play_as = ai_token;
 
         OttoBot { 
+            difficulty,
             depth,
-            play_as,
+            searcher: Searcher::new(TABLE_SIZE),
+            mcts,
+            weights: OttoWeights::default(),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn best_move(&mut self, board: &mut TootOttoBoard) -> OttoMove {
+        let evaluate = self.evaluator();
+        if let Some(mcts) = self.mcts.as_mut() {
+            return mcts.best_move(board, &evaluate);
         }
+        let (_, mov) = self.searcher.best_move(board, self.depth_for(board), &evaluate);
+        mov
+    }
+
+    ///Searches one ply deeper at a time until `millis` milliseconds are up
+    ///and returns the best move of the last search that finished, or with
+    ///Difficulty::Mcts runs tree search for that long
+    #[wasm_bindgen]
+    pub fn best_move_with_budget(&mut self, board: &mut TootOttoBoard, millis: u32) -> OttoMove {
+        self.with_budget(board, Budget::millis(millis))
     }
 
+    ///Like best_move_with_budget, but stops after searching `nodes` positions
+    ///so the move is the same on every machine
     #[wasm_bindgen]
-    pub fn best_move(&self, board: &mut TootOttoBoard, player: char) -> String{
-        let log = "OttoBot bestmove player: {} called".replace("{}", &player.to_string());
-        //console::log_1(&log.into());
-        //console::log_1(&"depth {}".replace("{}",&self.depth.to_string()).into());
-        let (score, mov, mov_char) = self.minimax(board, self.depth, i32::MIN, i32::MAX, player);
-        println!("Move: {} {}, Score: {}", mov, mov_char, score);
-        let mov_str = mov.to_string() + mov_char.to_string().as_str();
-        mov_str
+    pub fn best_move_with_node_budget(&mut self, board: &mut TootOttoBoard, nodes: u32) -> OttoMove {
+        self.with_budget(board, Budget::nodes(nodes as u64))
     }
 
+    ///Sets how many iterations best_move runs with Difficulty::Mcts
+    #[wasm_bindgen]
+    pub fn set_iterations(&mut self, iterations: u32) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_iterations(iterations);
+        }
+    }
+
+    ///Sets whether Difficulty::Mcts plays games out at random or guided by
+    ///the heuristic
+    #[wasm_bindgen]
+    pub fn set_rollout(&mut self, rollout: Rollout) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_rollout(rollout);
+        }
+    }
+
+    ///The bot's settings as a JavaScript object, see BotConfig
+    #[wasm_bindgen]
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(self)?)
+    }
+
+    ///Sets up a bot with settings written by to_js
+    #[wasm_bindgen]
+    pub fn from_js(value: JsValue) -> Result<OttoBot, JsValue> {
+        Ok(serde_wasm_bindgen::from_value(value)?)
+    }
+}
+
+impl Serialize for OttoBot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BotConfig { difficulty: self.difficulty }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OttoBot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OttoBot, D::Error> {
+        //the bot plays whichever side is to move, so the player doesn't matter
+        BotConfig::deserialize(deserializer).map(|config| OttoBot::new(config.difficulty, Player::One))
+    }
 }
 
 impl OttoBot {
-    fn evaluate(&self, board: &mut TootOttoBoard, player: char) -> i32 {
+    ///Searches `depth` plies ahead instead of the difficulty's depth
+    pub fn set_depth(&mut self, depth: u32) {
+        self.depth = depth;
+    }
+
+    ///Scores positions with `weights` from now on, forgetting the scores
+    ///earlier searches worked out with the old ones
+    pub fn set_weights(&mut self, weights: OttoWeights) {
+        self.weights = weights;
+        self.searcher = Searcher::new(TABLE_SIZE);
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.clear();
+        }
+    }
+
+    fn with_budget(&mut self, board: &mut TootOttoBoard, budget: Budget) -> OttoMove {
+        let evaluate = self.evaluator();
+        match self.mcts.as_mut() {
+            Some(mcts) => mcts.best_move_with_budget(board, budget, &evaluate),
+            None => self.searcher.best_move_with_budget(board, budget, &evaluate),
+        }
+    }
+
+    //evaluate with this bot's weights, for the searcher
+    fn evaluator(&self) -> impl Fn(&TootOttoBoard, Player) -> i32 {
+        let weights = self.weights;
+        move |board, player| Self::evaluate(board, player, &weights)
+    }
+
+    ///How deep to search `board`, shallower when tokens can go on any cell
+    fn depth_for(&self, board: &TootOttoBoard) -> u32 {
+        if board.gravity() { self.depth } else { cmp::min(self.depth, FREE_PLACEMENT_DEPTH) }
+    }
+
+    ///Heuristic value of `board` for `player`, used where the search stops
+    fn evaluate(board: &TootOttoBoard, player: Player, weights: &OttoWeights) -> i32 {
         let mut score = 0;
-        let opposite = if player == 'O' { 'T' } else { 'O' };
-        let score_multiplier = 1;
+        //the letter at the ends of player's word, and the one in the middle
+        let mine = Cell::from(player.letter());
+        let opposite = Cell::from(player.other().letter());
+        let center = game::center_columns(board.width());
 
         //score it similar to the has_winner function in TootOttoBoard
-        for row in (0..board.height() as usize) {
-            for col in (0..board.width() as usize){
+        for row in 0..board.height() as usize {
+            for col in 0..board.width() as usize {
 
                 //give incentive to play in the middle
-                if col == 3 || col == 2{
-                    score += 1 * score_multiplier;
+                if center.contains(&(col as u32)) {
+                    score += weights.center;
                 }
 
                 //check rows
@@ -59,26 +196,26 @@ impl OttoBot {
                 if col + 3 < board.width() as usize{
 
                     //check for 4 in a row
-                    if((board.get(row,col) == player)
+                    if (board.get(row,col) == mine)
                     && (board.get(row,col + 1) == opposite)
                     && (board.get(row,col + 2) == opposite)
-                    && (board.get(row,col + 3) == player)){
-                        score += 100000 * score_multiplier;
+                    && (board.get(row,col + 3) == mine) {
+                        score += weights.word;
                     }
 
                     //check for 3 in a row
-                    else if((board.get(row,col) == player)
+                    else if (board.get(row,col) == mine)
                     && (board.get(row,col + 1) == opposite)
-                    && (board.get(row,col + 2) == opposite)){
-                        score += 100 * score_multiplier;
+                    && (board.get(row,col + 2) == opposite) {
+                        score += weights.three;
                     }
 
                     //check blocking
-                    else if((board.get(row,col) == opposite)
-                    && (board.get(row,col + 1) == player)
-                    && (board.get(row,col + 2) == player)
-                    && (board.get(row,col +3) == player)){
-                        score += 500 * score_multiplier; //prioritize blocking over 3 in a row
+                    else if (board.get(row,col) == opposite)
+                    && (board.get(row,col + 1) == mine)
+                    && (board.get(row,col + 2) == mine)
+                    && (board.get(row,col +3) == mine) {
+                        score += weights.block; //prioritize blocking over 3 in a row
                     }
 
                 }
@@ -87,157 +224,139 @@ impl OttoBot {
                     
                     //--------check vertical---------
                     //check for 4 in a row
-                    if((board.get(row,col) == player)
+                    if (board.get(row,col) == mine)
                     && (board.get(row - 1,col) == opposite)
                     && (board.get(row - 2,col) == opposite)
-                    && (board.get(row - 3,col) == player)){
-                        score += 100000 * score_multiplier;
+                    && (board.get(row - 3,col) == mine) {
+                        score += weights.word;
                     }
 
                     //check for 3 in a row
-                    else if((board.get(row,col) == player)
+                    else if (board.get(row,col) == mine)
                     && (board.get(row - 1,col) == opposite)
-                    && (board.get(row - 2,col) == opposite)){
-                        score += 100 * score_multiplier;
+                    && (board.get(row - 2,col) == opposite) {
+                        score += weights.three;
                     }
 
                     //check for 2 in a row
-                    else if((board.get(row,col) == player)
-                    && (board.get(row - 1,col) == opposite)){
-                        score += 10 * score_multiplier;
+                    else if (board.get(row,col) == mine)
+                    && (board.get(row - 1,col) == opposite) {
+                        score += weights.two;
                     }
 
                     //check blocking
-                    else if((board.get(row,col) == opposite)
-                    && (board.get(row - 1,col) == player)
-                    && (board.get(row - 2,col) == player)
-                    && (board.get(row - 3,col) == player)){
-                        score += 500 * score_multiplier; //prioritize blocking over 3 in a row
+                    else if (board.get(row,col) == opposite)
+                    && (board.get(row - 1,col) == mine)
+                    && (board.get(row - 2,col) == mine)
+                    && (board.get(row - 3,col) == mine) {
+                        score += weights.block; //prioritize blocking over 3 in a row
                     }
 
 
                     //--------check up and right--------
                     if col + 3 < board.width() as usize{
                         //check for 4 in a row
-                        if((board.get(row,col) == player)
+                        if (board.get(row,col) == mine)
                         && (board.get(row - 1,col + 1) == opposite)
                         && (board.get(row - 2,col + 2) == opposite)
-                        && (board.get(row - 3,col + 3) == player)){
-                            score += 100000 * score_multiplier;
+                        && (board.get(row - 3,col + 3) == mine) {
+                            score += weights.word;
                         }
 
                         //check for 3 in a row
-                        else if((board.get(row,col) == player)
+                        else if (board.get(row,col) == mine)
                         && (board.get(row - 1,col + 1) == opposite)
-                        && (board.get(row - 2,col + 2) == opposite)){
-                            score += 100 * score_multiplier;
+                        && (board.get(row - 2,col + 2) == opposite) {
+                            score += weights.three;
                         }
 
                         //check for 2 in a row
-                        else if((board.get(row,col) == player)
-                        && (board.get(row - 1,col + 1) == opposite)){
-                            score += 10 * score_multiplier;
+                        else if (board.get(row,col) == mine)
+                        && (board.get(row - 1,col + 1) == opposite) {
+                            score += weights.two;
                         }
 
                         //check blocking
-                        else if((board.get(row,col) == opposite)
-                        && (board.get(row - 1,col + 1) == player)
-                        && (board.get(row - 2,col + 2) == player)
-                        && (board.get(row - 3,col + 3) == player)){
-                            score += 500 * score_multiplier; //prioritize blocking over 3 in a row
+                        else if (board.get(row,col) == opposite)
+                        && (board.get(row - 1,col + 1) == mine)
+                        && (board.get(row - 2,col + 2) == mine)
+                        && (board.get(row - 3,col + 3) == mine) {
+                            score += weights.block; //prioritize blocking over 3 in a row
                         }
                     }
 
                     //check up and left
                     if col as i32 - 3 >= 0{
                         //check for 4 in a row
-                        if((board.get(row,col) == player)
+                        if (board.get(row,col) == mine)
                         && (board.get(row - 1,col - 1) == opposite)
                         && (board.get(row - 2,col - 2) == opposite)
-                        && (board.get(row - 3,col - 3) == player)){
-                            score += 100000 * score_multiplier;
+                        && (board.get(row - 3,col - 3) == mine) {
+                            score += weights.word;
                         }
 
                         //check for 3 in a row
-                        else if((board.get(row,col) == player)
+                        else if (board.get(row,col) == mine)
                         && (board.get(row - 1,col - 1) == opposite)
-                        && (board.get(row - 2,col - 2) == opposite)){
-                            score += 100 * score_multiplier;
+                        && (board.get(row - 2,col - 2) == opposite) {
+                            score += weights.three;
                         }
 
                         //check for 2 in a row
-                        else if((board.get(row,col) == player)
-                        && (board.get(row - 1,col - 1) == opposite)){
-                            score += 10 * score_multiplier;
+                        else if (board.get(row,col) == mine)
+                        && (board.get(row - 1,col - 1) == opposite) {
+                            score += weights.two;
                         }
 
                         //check blocking
-                        else if((board.get(row,col) == opposite)
-                        && (board.get(row - 1,col - 1) == player)
-                        && (board.get(row - 2,col - 2) == player)
-                        && (board.get(row - 3,col - 3) == player)){
-                            score += 500 * score_multiplier; //prioritize blocking over 3 in a row
+                        else if (board.get(row,col) == opposite)
+                        && (board.get(row - 1,col - 1) == mine)
+                        && (board.get(row - 2,col - 2) == mine)
+                        && (board.get(row - 3,col - 3) == mine) {
+                            score += weights.block; //prioritize blocking over 3 in a row
                         }
                     }
                 }
 
             }
         }
-        score
-    }
-
-    fn minimax(&self, board: &mut TootOttoBoard, depth: u32,alpha: i32, beta: i32, player: char) -> (i32, u32, char) {
-        let mut alpha = alpha;
-        let mut beta = beta;
-        let mut best_move = 2;
-        let mut best_score = if player == 'O' { i32::MIN } else { i32::MAX };
-        let mut best_move_char = player;
-
-        if depth == 0 || board.is_terminal() {
-           best_score = {
-                if board.is_terminal(){
-                    board.game_value()
-                } else{
-                    self.evaluate(board, self.play_as)
-                }
-           };
-            return (best_score, 2, 'O'); //fight for otto
+
+        //in misere everything that would help spell the word hurts
+        if board.misere() { -score } else { score }
+    }
+}
+
+impl Bot<TootOttoBoard> for OttoBot {
+    fn choose_move(&mut self, board: &mut TootOttoBoard) -> OttoMove {
+        let evaluate = self.evaluator();
+        if let Some(mcts) = self.mcts.as_mut() {
+            return mcts.best_move(board, &evaluate);
         }
+        self.searcher.best_move(board, self.depth_for(board), &evaluate).1
+    }
 
-        for i in (0..board.width() as u32) {
-
-            //console::log_1(&"AI Trying for a move".into());
-            if board.allows_move(i) {
-
-                let tokens = ['O', 'T'];
-                for &token in tokens.iter() {
-                    board.perform_move_plz(i, token, player);
-                    let (score, _, _) = self.minimax(board, depth - 1,alpha, beta, if player == 'T' { 'O' } else { 'T' });
-                    board.undo_move(i as usize);
-                
-                    if player == 'O' {
-                        if score > best_score {
-                            best_score = score;
-                            best_move = i;
-                            best_move_char = token;
-                        }
-                        alpha = cmp::max(alpha,best_score );
-                    } else {
-                        if score < best_score {
-                            best_score = score;
-                            best_move = i;
-                            best_move_char = token;
-                        }
-                        beta = cmp::min(beta,best_score );
-                    }
+    fn choose_move_with_budget(&mut self, board: &mut TootOttoBoard, millis: u32) -> OttoMove {
+        self.with_budget(board, Budget::millis(millis))
+    }
 
-                    if beta <= alpha {
-                        break;
-                    }
-                }
+    fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    fn depth(&self, board: &TootOttoBoard) -> u32 {
+        self.depth_for(board)
+    }
+
+    ///With Difficulty::Mcts there's one report when the search ends, and
+    ///any depth limit means its usual number of iterations
+    fn analyze(&mut self, board: &mut TootOttoBoard, max_depth: u32, budget: Budget, report: &mut dyn FnMut(&SearchInfo<OttoMove>)) -> OttoMove {
+        let evaluate = self.evaluator();
+        match self.mcts.as_mut() {
+            Some(mcts) => {
+                let iterations = if max_depth == u32::MAX { u32::MAX } else { mcts.iterations() };
+                mcts.search(board, iterations, budget, &evaluate, report)
             }
+            None => self.searcher.deepen(board, max_depth, budget, &evaluate, report),
         }
-
-        (best_score, best_move, best_move_char)
     }
 }
\ No newline at end of file
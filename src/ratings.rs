@@ -0,0 +1,439 @@
+//! A ladder of Elo and Glicko-2 ratings for people and bots
+//!
+//! Ratings are kept separately for each game, board size and set of rules,
+//! in pools named like `Connect 4 7x6 PopOut misere win-4`. Every finished two-player game updates both players'
+//! Elo and Glicko-2 ratings in its pool, each game counting as a rating
+//! period of its own for Glicko-2. The ledger keeps every game it was given
+//! and is saved as JSON, natively to a file and in the browser as a
+//! JavaScript object.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::io;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use crate::record::{self, GameRecord};
+
+/// Where everyone starts, in both systems
+pub const INITIAL_RATING: f64 = 1500.0;
+
+// how far one Elo game can move a rating
+const ELO_K: f64 = 32.0;
+
+// a new player's Glicko-2 deviation and volatility
+const INITIAL_DEVIATION: f64 = 350.0;
+const INITIAL_VOLATILITY: f64 = 0.06;
+
+// how much the volatility may change, Glickman suggests 0.3 to 1.2
+const TAU: f64 = 0.5;
+
+// Glicko-2 works on a scale this many times smaller than Glicko's
+const GLICKO2_SCALE: f64 = 173.7178;
+
+// when the volatility's iteration stops
+const CONVERGENCE: f64 = 0.000001;
+
+/// Which rating the leaderboard sorts by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RatingSystem {
+    Elo,
+    Glicko2,
+}
+
+/// One player's standing in one pool
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub elo: f64,
+    pub glicko: f64,
+    /// Glicko-2's rating deviation, how unsure the glicko rating is
+    pub deviation: f64,
+    pub volatility: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// A game as the ledger keeps it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RatedGame {
+    pub pool: String,
+    pub date: String,
+    /// in turn order
+    pub players: [String; 2],
+    /// the first player's points, 1 for a win and 0.5 for a draw
+    pub score: f64,
+}
+
+/// Every pool's ratings and the games that made them
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ledger {
+    pools: BTreeMap<String, BTreeMap<String, Rating>>,
+    games: Vec<RatedGame>,
+}
+
+/// Why a game couldn't be rated or a ledger couldn't be read or saved. In
+/// JS it's thrown as an `Error` like `MoveError`.
+#[derive(Debug)]
+pub enum RatingError {
+    /// the record has no tag with this name
+    MissingTag(&'static str),
+    Unfinished,
+    NotTwoPlayers,
+    /// the record names this many players but this many names were given
+    WrongNameCount(usize, usize),
+    SamePlayer,
+    /// a score that isn't from 0 to 1
+    BadScore,
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            elo: INITIAL_RATING,
+            glicko: INITIAL_RATING,
+            deviation: INITIAL_DEVIATION,
+            volatility: INITIAL_VOLATILITY,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+        }
+    }
+}
+
+impl Rating {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn value(&self, system: RatingSystem) -> f64 {
+        match system {
+            RatingSystem::Elo => self.elo,
+            RatingSystem::Glicko2 => self.glicko,
+        }
+    }
+
+    // this rating after scoring `score` against `opponent`, both as they
+    // were before the game
+    fn after(&self, opponent: &Rating, score: f64) -> Rating {
+        let expected = 1.0 / (1.0 + 10f64.powf((opponent.elo - self.elo) / 400.0));
+        let (glicko, deviation, volatility) = glicko2(self, &[(*opponent, score)]);
+        let mut rating = Rating { elo: self.elo + ELO_K * (score - expected), glicko, deviation, volatility, ..*self };
+        match score {
+            s if s > 0.5 => rating.wins += 1,
+            s if s < 0.5 => rating.losses += 1,
+            _ => rating.draws += 1,
+        }
+        rating
+    }
+}
+
+// Glickman's Glicko-2 update for a rating period of the games in `results`,
+// each an opponent and the player's score against them, returning the new
+// rating, deviation and volatility
+fn glicko2(player: &Rating, results: &[(Rating, f64)]) -> (f64, f64, f64) {
+    let mu = (player.glicko - INITIAL_RATING) / GLICKO2_SCALE;
+    let phi = player.deviation / GLICKO2_SCALE;
+
+    // how much the games say and how far they pull the rating
+    let mut information = 0.0;
+    let mut surprise = 0.0;
+    for (opponent, score) in results {
+        let mu_j = (opponent.glicko - INITIAL_RATING) / GLICKO2_SCALE;
+        let phi_j = opponent.deviation / GLICKO2_SCALE;
+        let g = 1.0 / (1.0 + 3.0 * phi_j * phi_j / (PI * PI)).sqrt();
+        let expected = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+        information += g * g * expected * (1.0 - expected);
+        surprise += g * (score - expected);
+    }
+    let v = 1.0 / information;
+    let delta = v * surprise;
+
+    // the new volatility is the root of f, found by the Illinois algorithm
+    let a = (player.volatility * player.volatility).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2)) - (x - a) / (TAU * TAU)
+    };
+    let mut low = a;
+    let mut high = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * TAU) < 0.0 {
+            k += 1.0;
+        }
+        a - k * TAU
+    };
+    let (mut f_low, mut f_high) = (f(low), f(high));
+    while (high - low).abs() > CONVERGENCE {
+        let mid = low + (low - high) * f_low / (f_high - f_low);
+        let f_mid = f(mid);
+        if f_mid * f_high <= 0.0 {
+            low = high;
+            f_low = f_high;
+        } else {
+            f_low /= 2.0;
+        }
+        high = mid;
+        f_high = f_mid;
+    }
+    let volatility = (low / 2.0).exp();
+
+    let phi_star = (phi * phi + volatility * volatility).sqrt();
+    let phi_new = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+    let mu_new = mu + phi_new * phi_new * surprise;
+    (mu_new * GLICKO2_SCALE + INITIAL_RATING, phi_new * GLICKO2_SCALE, volatility)
+}
+
+/// The pool games of `game` on a board `board` played by `rules` are rated
+/// in, like `Connect 4 7x6 Classic win-4`. Games with different rules
+/// aren't rated against each other.
+pub fn pool_name(game: &str, board: &str, rules: &str) -> String {
+    format!("{} {} {}", game, board, rules).trim_end().to_string()
+}
+
+#[wasm_bindgen]
+impl Ledger {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Ledger {
+        Ledger::default()
+    }
+
+    /// Rates a game of `game` on a board `board` played by `rules`, like
+    /// "Connect 4", "7x6" and "PopOut misere" as game records write them.
+    /// `score` is the first player's points: 1 for a win, 0.5 for a draw and
+    /// 0 for a loss.
+    #[wasm_bindgen]
+    pub fn record(&mut self, game: &str, board: &str, rules: &str, first: &str, second: &str, score: f64) -> Result<(), RatingError> {
+        if first == second {
+            return Err(RatingError::SamePlayer);
+        }
+        if !(0.0..=1.0).contains(&score) {
+            return Err(RatingError::BadScore);
+        }
+        let pool = pool_name(game, board, rules);
+        let ratings = self.pools.entry(pool.clone()).or_default();
+        let before = [first, second].map(|name| ratings.get(name).copied().unwrap_or_default());
+        ratings.insert(first.to_string(), before[0].after(&before[1], score));
+        ratings.insert(second.to_string(), before[1].after(&before[0], 1.0 - score));
+        self.games.push(RatedGame { pool, date: record::today(), players: [first.to_string(), second.to_string()], score });
+        Ok(())
+    }
+
+    /// The ledger as a JavaScript object, to keep in localStorage
+    #[wasm_bindgen]
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        Ok(serde_wasm_bindgen::to_value(self)?)
+    }
+
+    /// Reads a ledger written by to_js
+    #[wasm_bindgen]
+    pub fn from_js(value: JsValue) -> Result<Ledger, JsValue> {
+        Ok(serde_wasm_bindgen::from_value(value)?)
+    }
+}
+
+impl Ledger {
+    /// Reads the ledger saved at `path`, or starts an empty one if there's
+    /// nothing there yet
+    pub fn load(path: &str) -> Result<Ledger, RatingError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Ledger::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RatingError> {
+        let json = serde_json::to_string_pretty(self)?;
+        Ok(fs::write(path, json)?)
+    }
+
+    /// Rates a game from its record, `names` being who played each seat in
+    /// the order the record's Players tag lists them
+    pub fn record_game(&mut self, game: &GameRecord, names: &[String]) -> Result<(), RatingError> {
+        let tag = |name| game.tag(name).ok_or(RatingError::MissingTag(name));
+        let (title, board, result) = (tag("Game")?, tag("Board")?, tag("Result")?);
+        // the rules and line length decide which pool the game is rated in
+        let mut rules = game.tag("Rules").unwrap_or("").to_string();
+        if let Some(length) = game.tag("WinLength") {
+            rules += &format!(" win-{}", length);
+        }
+        // like "X: Person, O: Computer", the seats in turn order
+        let seats: Vec<&str> = tag("Players")?.split(", ").map(|seat| seat.split(": ").next().unwrap_or(seat)).collect();
+        if seats.len() != 2 {
+            return Err(RatingError::NotTwoPlayers);
+        }
+        if names.len() != seats.len() {
+            return Err(RatingError::WrongNameCount(seats.len(), names.len()));
+        }
+        let score = match result {
+            "Draw" => 0.5,
+            _ if result == format!("{} wins", seats[0]) => 1.0,
+            _ if result == format!("{} wins", seats[1]) => 0.0,
+            _ => return Err(RatingError::Unfinished),
+        };
+        self.record(title, board, rules.trim(), &names[0], &names[1], score)
+    }
+
+    /// The names of every pool, in order
+    pub fn pools(&self) -> impl Iterator<Item = &str> {
+        self.pools.keys().map(String::as_str)
+    }
+
+    /// Everyone rated in `pool`, best first by `system`
+    pub fn leaderboard(&self, pool: &str, system: RatingSystem) -> Vec<(&str, Rating)> {
+        let mut board: Vec<(&str, Rating)> = self.pools.get(pool)
+            .map(|ratings| ratings.iter().map(|(name, &rating)| (name.as_str(), rating)).collect())
+            .unwrap_or_default();
+        board.sort_by(|a, b| b.1.value(system).total_cmp(&a.1.value(system)));
+        board
+    }
+}
+
+impl fmt::Display for RatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RatingError::MissingTag(name) => write!(f, "the game's record has no {} tag", name),
+            RatingError::Unfinished => write!(f, "only finished games can be rated"),
+            RatingError::NotTwoPlayers => write!(f, "only two-player games can be rated"),
+            RatingError::WrongNameCount(players, names) => write!(f, "the game had {} players but {} names were given", players, names),
+            RatingError::SamePlayer => write!(f, "a player can't be rated against themselves"),
+            RatingError::BadScore => write!(f, "scores go from 0 for a loss to 1 for a win"),
+            RatingError::Io(error) => write!(f, "{}", error),
+            RatingError::Json(error) => write!(f, "that isn't a ratings ledger: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RatingError {}
+
+impl From<io::Error> for RatingError {
+    fn from(error: io::Error) -> RatingError {
+        RatingError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for RatingError {
+    fn from(error: serde_json::Error) -> RatingError {
+        RatingError::Json(error)
+    }
+}
+
+impl From<RatingError> for JsValue {
+    fn from(error: RatingError) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rated(glicko: f64, deviation: f64) -> Rating {
+        Rating { glicko, deviation, ..Rating::default() }
+    }
+
+    fn finished_game(result: &str) -> GameRecord {
+        let mut game = GameRecord::new();
+        game.set_tag("Game", "Connect 4");
+        game.set_tag("Board", "7x6");
+        game.set_tag("Rules", "Classic");
+        game.set_tag("WinLength", "4");
+        game.set_tag("Players", "X: Person, O: Computer");
+        game.set_tag("Result", result);
+        game
+    }
+
+    #[test]
+    fn glicko2_matches_glickmans_example() {
+        let player = rated(1500.0, 200.0);
+        let results = [(rated(1400.0, 30.0), 1.0), (rated(1550.0, 100.0), 0.0), (rated(1700.0, 300.0), 0.0)];
+        let (glicko, deviation, volatility) = glicko2(&player, &results);
+        assert!((glicko - 1464.06).abs() < 0.01, "{}", glicko);
+        assert!((deviation - 151.52).abs() < 0.01, "{}", deviation);
+        assert!((volatility - 0.05999).abs() < 0.00001, "{}", volatility);
+    }
+
+    #[test]
+    fn elo_moves_both_players_the_same_amount() {
+        let mut ledger = Ledger::new();
+        ledger.record("Connect 4", "7x6", "Classic", "ann", "bob", 1.0).unwrap();
+        let board = ledger.leaderboard("Connect 4 7x6 Classic", RatingSystem::Elo);
+        assert_eq!(board[0].0, "ann");
+        assert_eq!(board[0].1.elo, INITIAL_RATING + ELO_K / 2.0);
+        assert_eq!(board[1].1.elo, INITIAL_RATING - ELO_K / 2.0);
+        assert_eq!((board[0].1.wins, board[1].1.losses), (1, 1));
+        assert!(board[0].1.glicko > INITIAL_RATING && board[0].1.deviation < INITIAL_DEVIATION);
+
+        ledger.record("Connect 4", "7x6", "Classic", "ann", "bob", 0.5).unwrap();
+        let board = ledger.leaderboard("Connect 4 7x6 Classic", RatingSystem::Elo);
+        assert!(board[0].1.elo < INITIAL_RATING + ELO_K / 2.0);
+        assert_eq!(board[0].1.elo + board[1].1.elo, 2.0 * INITIAL_RATING);
+    }
+
+    #[test]
+    fn records_are_scored_from_their_result() {
+        let names = ["ann".to_string(), "bob".to_string()];
+        let mut ledger = Ledger::new();
+        ledger.record_game(&finished_game("O wins"), &names).unwrap();
+        ledger.record_game(&finished_game("Draw"), &names).unwrap();
+        let scores: Vec<f64> = ledger.games.iter().map(|game| game.score).collect();
+        assert_eq!(scores, [0.0, 0.5]);
+        assert_eq!(ledger.pools().collect::<Vec<_>>(), ["Connect 4 7x6 Classic win-4"]);
+        assert_eq!(ledger.leaderboard("Connect 4 7x6 Classic win-4", RatingSystem::Glicko2)[0].0, "bob");
+
+        assert!(matches!(ledger.record_game(&finished_game("*"), &names), Err(RatingError::Unfinished)));
+        assert!(matches!(ledger.record_game(&finished_game("X wins"), &names[..1]), Err(RatingError::WrongNameCount(2, 1))));
+        assert!(matches!(ledger.record_game(&GameRecord::new(), &names), Err(RatingError::MissingTag("Game"))));
+        let mut three = finished_game("X wins");
+        three.set_tag("Players", "X: ann, O: bob, Y: cat");
+        assert!(matches!(ledger.record_game(&three, &names), Err(RatingError::NotTwoPlayers)));
+    }
+
+    #[test]
+    fn each_set_of_rules_has_its_own_pool() {
+        let names = ["ann".to_string(), "bob".to_string()];
+        let mut ledger = Ledger::new();
+        ledger.record_game(&finished_game("X wins"), &names).unwrap();
+        let mut popout = finished_game("X wins");
+        popout.set_tag("Rules", "PopOut misere");
+        ledger.record_game(&popout, &names).unwrap();
+        // records saved before the rules were tagged
+        let mut old = GameRecord::new();
+        for (tag, value) in [("Game", "Connect 4"), ("Board", "7x6"), ("Players", "X: Person, O: Computer"), ("Result", "X wins")] {
+            old.set_tag(tag, value);
+        }
+        ledger.record_game(&old, &names).unwrap();
+        assert_eq!(ledger.pools().collect::<Vec<_>>(), ["Connect 4 7x6", "Connect 4 7x6 Classic win-4", "Connect 4 7x6 PopOut misere win-4"]);
+        for pool in ledger.pools() {
+            assert_eq!(ledger.leaderboard(pool, RatingSystem::Elo)[0].1.games(), 1);
+        }
+    }
+
+    #[test]
+    fn bad_games_arent_rated() {
+        let mut ledger = Ledger::new();
+        assert!(matches!(ledger.record("Connect 4", "7x6", "Classic", "ann", "ann", 1.0), Err(RatingError::SamePlayer)));
+        assert!(matches!(ledger.record("Connect 4", "7x6", "Classic", "ann", "bob", 1.5), Err(RatingError::BadScore)));
+        assert_eq!(ledger, Ledger::new());
+    }
+
+    #[test]
+    fn ledgers_are_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("connect4-ledger-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(Ledger::load(path).unwrap(), Ledger::new());
+        let mut ledger = Ledger::new();
+        ledger.record("TOOT and OTTO", "6x4", "Standard", "ann", "bob", 0.5).unwrap();
+        ledger.save(path).unwrap();
+        let loaded = Ledger::load(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap(), ledger);
+    }
+}
@@ -0,0 +1,120 @@
+//! Game records, written like a chess PGN
+//!
+//! A record is a block of `[Name "value"]` tags, one per line, then a blank
+//! line and the moves in the order they were played, separated by spaces:
+//!
+//! ```text
+//! [Game "Connect 4"]
+//! [Board "7x6"]
+//! [Start "7/7/7/7/7/7/7 X"]
+//! [Result "X wins"]
+//!
+//! 3 3 4 4 5 5 6
+//! ```
+//!
+//! Moves are written the way each board's move list writes them: a column,
+//! `p` and a column for a pop, `(row,col)` for a placement, with the token
+//! after it in TOOT and OTTO. Each board knows how to write the tags for its
+//! rules and rebuild itself from them with `from_record`.
+
+use std::fmt;
+use crate::board::NotationError;
+
+// moves per line when a record is written out
+const MOVES_PER_LINE: usize = 16;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameRecord {
+    // in the order they're written
+    tags: Vec<(String, String)>,
+    moves: Vec<String>,
+}
+
+impl GameRecord {
+    pub fn new() -> GameRecord {
+        GameRecord::default()
+    }
+
+    /// The value of the tag called `name`, if the record has one
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the tag called `name`, adding it after the others if it's new
+    pub fn set_tag(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value,
+            None => self.tags.push((name.to_string(), value)),
+        }
+    }
+
+    /// Every move in the order it was played
+    pub fn moves(&self) -> &[String] {
+        &self.moves
+    }
+
+    pub fn set_moves(&mut self, moves: Vec<String>) {
+        self.moves = moves;
+    }
+
+    /// Reads a record written by `to_string`
+    pub fn parse(text: &str) -> Result<GameRecord, NotationError> {
+        let mut record = GameRecord::new();
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                let (name, value) = line.strip_prefix('[')
+                    .and_then(|line| line.strip_suffix(']'))
+                    .and_then(|tag| tag.split_once(' '))
+                    .ok_or(NotationError::Unreadable)?;
+                let value = value.strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .ok_or(NotationError::Unreadable)?;
+                record.set_tag(name, value);
+            } else {
+                record.moves.extend(line.split_whitespace().map(String::from));
+            }
+        }
+        Ok(record)
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in self.tags.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+        for line in self.moves.chunks(MOVES_PER_LINE) {
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Today's date as the Date tag writes it, e.g. 2024.03.09
+pub fn today() -> String {
+    let days = days_since_epoch();
+    // days since 1970-01-01 to a calendar date, see Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn days_since_epoch() -> i64 {
+    (js_sys::Date::now() / 86_400_000.0) as i64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn days_since_epoch() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / 86_400) as i64
+}
@@ -0,0 +1,276 @@
+//! Alpha-beta minimax over any `Game`
+//!
+//! Scores are from the point of view of the player the search is for: a win
+//! for them is `i32::MAX`, a loss `i32::MIN`, and positions at the depth limit
+//! are scored by the game's heuristic. Positions are cached in a transposition
+//! table that lives as long as the `Searcher`, and a `Budget` can stop the
+//! search part way through.
+//!
+//! With more than two players the search is paranoid: the player it's for
+//! maximizes and every other player minimizes, as if they'd all teamed up.
+
+use std::cmp;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use crate::board::Difficulty;
+use crate::budget::Budget;
+use crate::game::{Game, Outcome};
+use crate::tt::{Bound, Entry, TranspositionTable};
+
+/// Anything that can pick moves for one side of a game
+pub trait Bot<G: Game> {
+    /// Picks a move for the side to move
+    fn choose_move(&mut self, game: &mut G) -> G::Move;
+
+    /// Like choose_move, but thinks for about `millis` milliseconds
+    fn choose_move_with_budget(&mut self, game: &mut G, millis: u32) -> G::Move;
+
+    /// How well the bot was asked to play, for game records
+    fn difficulty(&self) -> Difficulty;
+
+    /// How many plies choose_move searches ahead on `game`
+    fn depth(&self, game: &G) -> u32;
+
+    /// Searches one ply deeper at a time up to `max_depth` or until `budget`
+    /// runs out, calls `report` as each depth finishes, and returns the best
+    /// move of the last one
+    fn analyze(&mut self, game: &mut G, max_depth: u32, budget: Budget, report: &mut dyn FnMut(&SearchInfo<G::Move>)) -> G::Move;
+}
+
+/// What a search found once it finished a depth
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchInfo<M> {
+    pub depth: u32,
+    /// i32::MAX for a forced win, i32::MIN for a forced loss
+    pub score: i32,
+    /// positions searched so far, over every depth
+    pub nodes: u64,
+    /// the best line found, the best move first
+    pub pv: Vec<M>,
+}
+
+/// How a bot was set up, which is what's kept when it's serialized. Its
+/// transposition table starts out empty again when it's loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BotConfig {
+    pub difficulty: Difficulty,
+}
+
+pub struct Searcher<G: Game> {
+    // kept between moves, positions searched last turn are reused
+    table: TranspositionTable<G::Move>,
+    // limits the search while best_move_with_budget is deepening
+    budget: Budget,
+    // the player the table's scores are for, it's cleared when that changes
+    root: Option<G::Player>,
+    // set when some line stopped at the depth limit rather than at the end of the game
+    cut_off: bool,
+    // positions searched since the last search started
+    nodes: u64,
+}
+
+impl<G: Game> Searcher<G> {
+    /// Creates a searcher whose transposition table has `table_size` slots
+    pub fn new(table_size: usize) -> Searcher<G> {
+        Searcher {
+            table: TranspositionTable::new(table_size),
+            budget: Budget::unlimited(),
+            root: None,
+            cut_off: false,
+            nodes: 0,
+        }
+    }
+
+    /// Searches `depth` plies ahead and returns the best move for the side to
+    /// move with its score. Positions at the depth limit are scored by
+    /// `evaluate(game, player)`, `player` being the side the search is for.
+    pub fn best_move<F>(&mut self, game: &mut G, depth: u32, evaluate: &F) -> (i32, G::Move)
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        let player = game.side_to_move();
+        self.set_root(player);
+        let (score, mv) = self.minimax(game, depth, i32::MIN, i32::MAX, player, evaluate);
+        (score, mv.expect("no legal moves"))
+    }
+
+    /// Searches one ply deeper at a time until `budget` runs out and returns the
+    /// best move of the last search that finished. Stops early once a win or
+    /// loss is forced, or a search reached the end of every line.
+    pub fn best_move_with_budget<F>(&mut self, game: &mut G, budget: Budget, evaluate: &F) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        self.deepen(game, u32::MAX, budget, evaluate, &mut |_| ())
+    }
+
+    /// Like best_move_with_budget, but stops after `max_depth` plies and calls
+    /// `report` with the score and best line as each depth finishes
+    pub fn deepen<F>(&mut self, game: &mut G, max_depth: u32, budget: Budget, evaluate: &F, report: &mut dyn FnMut(&SearchInfo<G::Move>)) -> G::Move
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        let player = game.side_to_move();
+        self.set_root(player);
+        self.nodes = 0;
+
+        // depth 1 always runs to completion so there is a move to return
+        self.cut_off = false;
+        let (mut score, mut best_move) = self.minimax(game, 1, i32::MIN, i32::MAX, player, evaluate);
+        self.budget = budget;
+        let mut depth = 1;
+        report(&self.info(game, depth, score, best_move));
+        while self.cut_off && score != i32::MAX && score != i32::MIN && depth < max_depth {
+            depth += 1;
+            self.cut_off = false;
+            let result = self.minimax(game, depth, i32::MIN, i32::MAX, player, evaluate);
+            if self.budget.exhausted() {
+                break;
+            }
+            (score, best_move) = result;
+            report(&self.info(game, depth, score, best_move));
+        }
+        self.budget = Budget::unlimited();
+        best_move.expect("no legal moves")
+    }
+
+    // what the search that just finished found, following the table's best
+    // moves from `best_move` for the rest of the line
+    fn info(&self, game: &mut G, depth: u32, score: i32, best_move: Option<G::Move>) -> SearchInfo<G::Move> {
+        let mut pv: Vec<G::Move> = best_move.into_iter().collect();
+        if let Some(mv) = best_move {
+            game.play(mv);
+        }
+        while !pv.is_empty() && pv.len() < depth as usize && game.outcome() == Outcome::InProgress {
+            let key = game.zobrist_key() ^ game.side_key();
+            let mv = match self.table.probe(key).and_then(|entry| entry.best_move) {
+                Some(mv) if game.legal_moves().contains(&mv) => mv,
+                _ => break,
+            };
+            game.play(mv);
+            pv.push(mv);
+        }
+        for &mv in pv.iter().rev() {
+            game.undo(mv);
+        }
+        SearchInfo { depth, score, nodes: self.nodes, pv }
+    }
+
+    fn set_root(&mut self, player: G::Player) {
+        if self.root != Some(player) {
+            self.table.clear();
+            self.root = Some(player);
+        }
+    }
+
+    fn minimax<F>(&mut self, game: &mut G, depth: u32, alpha: i32, beta: i32, root: G::Player, evaluate: &F) -> (i32, Option<G::Move>)
+    where
+        F: Fn(&G, G::Player) -> i32,
+    {
+        self.nodes += 1;
+        if self.budget.tick() {
+            return (0, None); //out of time, the caller throws this search away
+        }
+        match game.outcome() {
+            Outcome::Win(winner) => return (if winner == root { i32::MAX } else { i32::MIN }, None),
+            Outcome::Draw => return (0, None),
+            Outcome::InProgress => (),
+        }
+        if depth == 0 {
+            self.cut_off = true;
+            return (evaluate(game, root), None);
+        }
+
+        let maximizing = game.side_to_move() == root;
+        let (alpha_orig, beta_orig) = (alpha, beta);
+        let mut alpha = alpha;
+        let mut beta = beta;
+
+        let mut moves = game.legal_moves();
+        if moves.is_empty() {
+            return (0, None); //nothing to play, nobody can make progress
+        }
+
+        let key = game.zobrist_key() ^ game.side_key();
+        if let Some(entry) = self.table.probe(key) {
+            if let Some(mv) = entry.best_move.filter(|mv| moves.contains(mv)) {
+                if entry.depth >= depth {
+                    match entry.bound {
+                        Bound::Exact => alpha = beta,
+                        Bound::Lower => alpha = cmp::max(alpha, entry.score),
+                        Bound::Upper => beta = cmp::min(beta, entry.score),
+                    }
+                    if alpha >= beta {
+                        // a score that came from the depth limit still needs
+                        // deepening, even when it's taken from the table
+                        self.cut_off |= !entry.complete;
+                        return (entry.score, Some(mv));
+                    }
+                }
+                // try last search's best move first, it's the likeliest cutoff
+                moves.retain(|&other| other != mv);
+                moves.insert(0, mv);
+            }
+        }
+
+        let mut best_move = *moves.choose(&mut rand::thread_rng()).unwrap();
+        let mut best_score = if maximizing { i32::MIN } else { i32::MAX };
+        // whether the lines under this position alone hit the depth limit
+        let cut_off_above = std::mem::replace(&mut self.cut_off, false);
+
+        for &mv in moves.iter() {
+            game.play(mv);
+            let score = self.minimax(game, depth - 1, alpha, beta, root, evaluate).0;
+            game.undo(mv);
+            if self.budget.exhausted() {
+                return (best_score, Some(best_move));
+            }
+            if maximizing {
+                if score > best_score {
+                    best_move = mv;
+                    best_score = score;
+                }
+                alpha = cmp::max(alpha, best_score);
+            } else {
+                if score < best_score {
+                    best_move = mv;
+                    best_score = score;
+                }
+                beta = cmp::min(beta, best_score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= alpha_orig {
+            Bound::Upper
+        } else if best_score >= beta_orig {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let complete = !self.cut_off;
+        self.cut_off |= cut_off_above;
+        self.table.store(Entry { key, depth, score: best_score, bound, best_move: Some(best_move), complete });
+
+        (best_score, Some(best_move))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect4::Connect4Board;
+
+    #[test]
+    fn deepening_goes_on_after_table_hits() {
+        let mut board = Connect4Board::from_notation("7/7/7/7/7/3X3 O").unwrap();
+        let mut searcher = Searcher::new(1 << 16);
+        let evaluate = |board: &Connect4Board, player| board.discs(player).count_ones() as i32;
+        searcher.deepen(&mut board, u32::MAX, Budget::nodes(5000), &evaluate, &mut |_| ());
+        let mut depths = Vec::new();
+        searcher.deepen(&mut board, 6, Budget::unlimited(), &evaluate, &mut |info| depths.push(info.depth));
+        assert_eq!(depths, (1..=6).collect::<Vec<u32>>());
+    }
+}
@@ -0,0 +1,532 @@
+//! Exact solver for the standard 7x6 board
+//!
+//! Negamax with alpha-beta pruning over a compact copy of the position,
+//! searched with null windows so every node only has to answer "better or
+//! worse than this score". Moves are tried center first, then by how many
+//! winning spots they create, and bounds are cached in a transposition table so
+//! transpositions are only solved once.
+//!
+//! Scores follow the usual convention: positive if the side to move wins,
+//! negative if it loses, 0 for a draw, and the sooner the win the larger the
+//! score (a win with your last disc scores 1).
+
+use std::fmt;
+use std::sync::Mutex;
+use crate::board::Player;
+use crate::connect4::{Connect4Board, Variant};
+use lazy_static::lazy_static;
+use wasm_bindgen::prelude::*;
+
+const WIDTH: u32 = 7;
+const HEIGHT: u32 = 6;
+const CELLS: i32 = (WIDTH * HEIGHT) as i32;
+const MIN_SCORE: i32 = -CELLS / 2 + 3;
+const MAX_SCORE: i32 = (CELLS + 1) / 2 - 3;
+
+// entries in the transposition table, rounded up to a prime
+const TABLE_SIZE: usize = 1 << 22;
+
+// columns to try first: center out
+const COLUMN_ORDER: [u32; WIDTH as usize] = [3, 2, 4, 1, 5, 0, 6];
+
+const BOTTOM_MASK: u64 = bottom_mask();
+const BOARD_MASK: u64 = BOTTOM_MASK * ((1 << HEIGHT) - 1);
+
+const fn bottom_mask() -> u64 {
+    let mut mask = 0;
+    let mut col = 0;
+    while col < WIDTH {
+        mask |= 1 << (col * (HEIGHT + 1));
+        col += 1;
+    }
+    mask
+}
+
+fn column_mask(col: u32) -> u64 {
+    ((1 << HEIGHT) - 1) << (col * (HEIGHT + 1))
+}
+
+fn top_mask(col: u32) -> u64 {
+    1 << (HEIGHT - 1 + col * (HEIGHT + 1))
+}
+
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// Why a board can't be solved. In JS it's thrown as an `Error` like
+/// `MoveError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    /// only lines of four on the standard 7x6 board
+    NotStandardSize,
+    /// PopOut, Pop 10, misère, gravity-free and three- or four-player games
+    UnsupportedRules,
+}
+
+/// Game-theoretic value of a position for the side to move
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct Solution {
+    score: i32,
+    verdict: Verdict,
+    distance: u32,
+    best_move: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl Solution {
+    /// Solver score, see the module docs
+    #[wasm_bindgen]
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    #[wasm_bindgen]
+    pub fn verdict(&self) -> Verdict {
+        self.verdict
+    }
+
+    /// Number of moves (both players) until the game ends with perfect play
+    #[wasm_bindgen]
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+
+    /// Column that keeps the verdict with the shortest win or longest loss,
+    /// None if the game is already over
+    #[wasm_bindgen]
+    pub fn best_move(&self) -> Option<u32> {
+        self.best_move
+    }
+}
+
+impl Solution {
+    fn new(score: i32, moves: u32, best_move: Option<u32>) -> Solution {
+        // the winner's final disc is move number CELLS + 2 - 2 * |score|, minus
+        // one when that lands on an odd move
+        let (verdict, last_move) = match score {
+            0 => (Verdict::Draw, CELLS as u32),
+            s if s > 0 => (Verdict::Win, (CELLS + 2 - 2 * s) as u32 - (moves + 1) % 2),
+            s => (Verdict::Loss, (CELLS + 2 + 2 * s) as u32 - moves % 2),
+        };
+        Solution {
+            score,
+            verdict,
+            distance: last_move - moves,
+            best_move,
+        }
+    }
+}
+
+lazy_static! {
+    static ref SOLVER: Mutex<Solver> = Mutex::new(Solver::new());
+}
+
+/// Solves `board`, which must be classic two-player connect four on the
+/// standard 7x6 board with Player::One (X) moving first. The transposition
+/// table is kept between calls.
+#[wasm_bindgen]
+pub fn solve(board: &Connect4Board) -> Result<Solution, SolveError> {
+    SOLVER.lock().unwrap().solve(board)
+}
+
+/// Whether the solver handles `board`'s size and rules
+pub fn check_solvable(board: &Connect4Board) -> Result<(), SolveError> {
+    if board.width() != WIDTH || board.height() != HEIGHT || board.win_length() != 4 {
+        Err(SolveError::NotStandardSize)
+    } else if board.variant() != Variant::Classic || board.misere() || !board.gravity() || board.players() != 2 {
+        Err(SolveError::UnsupportedRules)
+    } else {
+        Ok(())
+    }
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::NotStandardSize => write!(f, "only connect four on the standard 7x6 board can be solved"),
+            SolveError::UnsupportedRules => write!(f, "only classic two-player connect four can be solved"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+impl From<SolveError> for JsValue {
+    fn from(error: SolveError) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
+
+// bitboards in the same layout as Connect4Board, but only for the side to move
+#[derive(Clone, Copy)]
+struct Position {
+    current: u64,
+    mask: u64,
+    moves: u32,
+}
+
+impl Position {
+    fn from_board(board: &Connect4Board) -> Result<Position, SolveError> {
+        check_solvable(board)?;
+        let x = board.discs(Player::One) as u64;
+        let o = board.discs(Player::Two) as u64;
+        let moves = (x | o).count_ones();
+        Ok(Position {
+            current: if moves.is_multiple_of(2) { x } else { o },
+            mask: x | o,
+            moves,
+        })
+    }
+
+    fn can_play(&self, col: u32) -> bool {
+        self.mask & top_mask(col) == 0
+    }
+
+    fn play(&mut self, mv: u64) {
+        self.current ^= self.mask;
+        self.mask |= mv;
+        self.moves += 1;
+    }
+
+    fn play_col(&mut self, col: u32) {
+        self.play((self.mask + (1 << (col * (HEIGHT + 1)))) & column_mask(col));
+    }
+
+    fn key(&self) -> u64 {
+        self.current + self.mask
+    }
+
+    fn possible(&self) -> u64 {
+        (self.mask + BOTTOM_MASK) & BOARD_MASK
+    }
+
+    fn winning_cells(&self) -> u64 {
+        winning_cells(self.current, self.mask)
+    }
+
+    fn opponent_winning_cells(&self) -> u64 {
+        winning_cells(self.current ^ self.mask, self.mask)
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.winning_cells() & self.possible() != 0
+    }
+
+    fn is_winning_move(&self, col: u32) -> bool {
+        self.winning_cells() & self.possible() & column_mask(col) != 0
+    }
+
+    // playable cells that don't hand the opponent an immediate win
+    fn non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_wins = self.opponent_winning_cells();
+        let forced = possible & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // two threats at once, nothing saves us
+                return 0;
+            }
+            possible = forced;
+        }
+        // never play right below a cell the opponent wins with
+        possible & !(opponent_wins >> 1)
+    }
+
+    // number of winning cells the side to move would have after playing `mv`
+    fn move_score(&self, mv: u64) -> u32 {
+        winning_cells(self.current | mv, self.mask).count_ones()
+    }
+}
+
+// empty cells that would complete a line of four for `position`
+fn winning_cells(position: u64, mask: u64) -> u64 {
+    // vertical
+    let mut cells = (position << 1) & (position << 2) & (position << 3);
+
+    for shift in [HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+        let pair = (position << shift) & (position << (2 * shift));
+        cells |= pair & (position << (3 * shift));
+        cells |= pair & (position >> shift);
+        let pair = (position >> shift) & (position >> (2 * shift));
+        cells |= pair & (position << shift);
+        cells |= pair & (position >> (3 * shift));
+    }
+
+    cells & (BOARD_MASK ^ mask)
+}
+
+// at most WIDTH moves, kept sorted by score so the best is popped first
+struct MoveSorter {
+    entries: [(u64, u32); WIDTH as usize],
+    size: usize,
+}
+
+impl MoveSorter {
+    fn new() -> MoveSorter {
+        MoveSorter { entries: [(0, 0); WIDTH as usize], size: 0 }
+    }
+
+    fn add(&mut self, mv: u64, score: u32) {
+        let mut pos = self.size;
+        while pos > 0 && self.entries[pos - 1].1 > score {
+            self.entries[pos] = self.entries[pos - 1];
+            pos -= 1;
+        }
+        self.entries[pos] = (mv, score);
+        self.size += 1;
+    }
+
+    fn next(&mut self) -> Option<u64> {
+        if self.size == 0 {
+            return None;
+        }
+        self.size -= 1;
+        Some(self.entries[self.size].0)
+    }
+}
+
+// Keeps one bound per position, packed as the 49 bit key followed by an 8 bit
+// value. A value of 0 marks an empty slot.
+struct TranspositionTable {
+    entries: Vec<u64>,
+}
+
+impl TranspositionTable {
+    fn new() -> TranspositionTable {
+        TranspositionTable { entries: vec![0; next_prime(TABLE_SIZE)] }
+    }
+
+    fn put(&mut self, key: u64, value: i32) {
+        let index = (key % self.entries.len() as u64) as usize;
+        self.entries[index] = key << 8 | value as u64;
+    }
+
+    fn get(&self, key: u64) -> i32 {
+        let entry = self.entries[(key % self.entries.len() as u64) as usize];
+        if entry >> 8 == key { (entry & 0xff) as i32 } else { 0 }
+    }
+}
+
+fn next_prime(n: usize) -> usize {
+    let is_prime = |n: usize| (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d));
+    (n..).find(|&n| is_prime(n)).unwrap()
+}
+
+pub struct Solver {
+    table: TranspositionTable,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            table: TranspositionTable::new(),
+        }
+    }
+
+    /// Solves `board` and picks the best column for the side to move
+    pub fn solve(&mut self, board: &Connect4Board) -> Result<Solution, SolveError> {
+        let position = Position::from_board(board)?;
+        if board.has_winner() {
+            return Ok(Solution::new(-(CELLS + 2 - position.moves as i32) / 2, position.moves, None));
+        }
+        if position.moves == CELLS as u32 {
+            return Ok(Solution::new(0, position.moves, None));
+        }
+
+        let score = self.score(&position);
+        // the first column, center out, whose child scores no worse for the
+        // opponent than `-score` keeps the value; one null window each is enough
+        let best_move = COLUMN_ORDER.iter().copied().find(|&col| {
+            if !position.can_play(col) {
+                return false;
+            }
+            if position.is_winning_move(col) {
+                return (CELLS + 1 - position.moves as i32) / 2 == score;
+            }
+            let mut next = position;
+            next.play_col(col);
+            if next.can_win_next() {
+                return -(CELLS + 1 - next.moves as i32) / 2 == score;
+            }
+            self.negamax(&next, -score, -score + 1) <= -score
+        });
+
+        Ok(Solution::new(score, position.moves, best_move))
+    }
+
+    // narrows [min, max] with null-window searches until the exact score is known
+    fn score(&mut self, position: &Position) -> i32 {
+        if position.can_win_next() {
+            return (CELLS + 1 - position.moves as i32) / 2;
+        }
+        let mut min = -(CELLS - position.moves as i32) / 2;
+        let mut max = (CELLS + 1 - position.moves as i32) / 2;
+
+        while min < max {
+            let mut med = min + (max - min) / 2;
+            // probe near 0 first, most positions are close to a draw
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+            let result = self.negamax(position, med, med + 1);
+            if result <= med {
+                max = result;
+            } else {
+                min = result;
+            }
+        }
+        min
+    }
+
+    // the side to move must not be able to win with its next move
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        let next = position.non_losing_moves();
+        if next == 0 {
+            return -(CELLS - position.moves as i32) / 2;
+        }
+        if position.moves as i32 >= CELLS - 2 {
+            return 0;
+        }
+
+        // we can't lose on the opponent's next move
+        let min = -(CELLS - 2 - position.moves as i32) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        // we can't win with our next move
+        let mut max = (CELLS - 1 - position.moves as i32) / 2;
+        let key = position.key();
+        let cached = self.table.get(key);
+        if cached != 0 {
+            if cached > MAX_SCORE - MIN_SCORE + 1 {
+                let lower = cached + 2 * MIN_SCORE - MAX_SCORE - 2;
+                if alpha < lower {
+                    alpha = lower;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+            } else {
+                max = cached + MIN_SCORE - 1;
+            }
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        let mut moves = MoveSorter::new();
+        for &col in COLUMN_ORDER.iter().rev() {
+            let mv = next & column_mask(col);
+            if mv != 0 {
+                moves.add(mv, position.move_score(mv));
+            }
+        }
+
+        while let Some(mv) = moves.next() {
+            let mut child = *position;
+            child.play(mv);
+            let score = -self.negamax(&child, -beta, -alpha);
+            if score >= beta {
+                // store lower bounds above the range of upper bounds
+                self.table.put(key, score + MAX_SCORE - 2 * MIN_SCORE + 2);
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        self.table.put(key, alpha - MIN_SCORE + 1);
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(moves: &str) -> Connect4Board {
+        Connect4Board::from_notation(moves).unwrap()
+    }
+
+    #[test]
+    fn takes_an_immediate_win() {
+        let solution = solve(&board("343434")).unwrap();
+        assert_eq!(solution.verdict(), Verdict::Win);
+        assert_eq!(solution.score(), 18);
+        assert_eq!(solution.distance(), 1);
+        assert_eq!(solution.best_move(), Some(3));
+    }
+
+    #[test]
+    fn sees_a_double_threat_is_lost() {
+        // X's three along the bottom can be finished at either end
+        let solution = solve(&board("11223")).unwrap();
+        assert_eq!(solution.verdict(), Verdict::Loss);
+        assert_eq!(solution.score(), -18);
+        assert_eq!(solution.distance(), 2);
+    }
+
+    #[test]
+    fn blocks_the_only_threat() {
+        let solution = solve(&board("34343")).unwrap();
+        assert_eq!(solution.best_move(), Some(3));
+    }
+
+    #[test]
+    fn best_move_keeps_the_value() {
+        let mut position = board("333333222222044440");
+        let solution = solve(&position).unwrap();
+        let col = solution.best_move().unwrap();
+        position.try_play(col, position.side_to_move()).unwrap();
+        assert_eq!(solve(&position).unwrap().score(), -solution.score());
+    }
+
+    #[test]
+    fn finished_games_have_no_move() {
+        let solution = solve(&board("3434343")).unwrap();
+        assert_eq!(solution.verdict(), Verdict::Loss);
+        assert_eq!(solution.best_move(), None);
+    }
+
+    #[test]
+    fn rejects_boards_it_cant_solve() {
+        let large = Connect4Board::with_dimensions(8, 7).unwrap();
+        assert_eq!(solve(&large).unwrap_err(), SolveError::NotStandardSize);
+        let five = Connect4Board::with_win_length(7, 6, 5).unwrap();
+        assert_eq!(solve(&five).unwrap_err(), SolveError::NotStandardSize);
+
+        let popout = Connect4Board::with_variant(7, 6, 4, Variant::PopOut).unwrap();
+        let mut misere = board("");
+        misere.set_misere(true);
+        let mut free = board("");
+        free.set_gravity(false);
+        let mut three = board("");
+        three.set_players(3).unwrap();
+        for unsupported in [popout, misere, free, three] {
+            assert_eq!(solve(&unsupported).unwrap_err(), SolveError::UnsupportedRules);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Matches between two engine setups, for tuning the heuristics
+//!
+//! A match plays its games from a set of balanced openings, the lines a
+//! shallow search can't tell apart, and the setups swap seats every game so
+//! each opening is played once from each side. The result is the first
+//! setup's wins, draws and losses, its score with a 95% confidence interval,
+//! and the Elo difference that score suggests.
+
+use std::collections::HashSet;
+use std::fmt;
+use crate::board::{Difficulty, Player};
+use crate::budget::Budget;
+use crate::cli::CliGame;
+use crate::connect4::{Connect4AI, Connect4Board, Connect4Weights};
+use crate::engine::EngineGame;
+use crate::game::{Game, Outcome};
+use crate::mcts::Rollout;
+use crate::otto::TootOttoBoard;
+use crate::ottobot::{OttoBot, OttoWeights};
+use crate::search::Bot;
+
+// how deep the search looks when judging whether an opening is balanced
+const BALANCE_DEPTH: u32 = 4;
+
+// standard deviations either side of the score for a 95% interval
+const Z_95: f64 = 1.96;
+
+/// How one side of a match plays
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EngineSetup<W> {
+    pub difficulty: Difficulty,
+    /// searches this deep instead of the difficulty's depth
+    pub depth: Option<u32>,
+    /// runs this many iterations a move with Difficulty::Mcts
+    pub iterations: Option<u32>,
+    pub rollout: Option<Rollout>,
+    pub weights: W,
+}
+
+/// A game whose engine's heuristic can be reweighted for a match
+pub trait TournamentGame: EngineGame + CliGame<Player = Player> {
+    type Weights: Copy + Default + fmt::Debug;
+
+    /// Sets the weight called `name`, false if there's no such weight
+    fn set_weight(weights: &mut Self::Weights, name: &str, value: i32) -> bool;
+
+    fn contestant(setup: &EngineSetup<Self::Weights>) -> Self::Ai;
+}
+
+/// How one game went for the first setup
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// The first setup's results over a match
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// The lines of `plies` moves from `start` that end in different positions
+/// and that a shallow search scores closest to even: none where it finds a
+/// forced result, and of the rest the more even half
+pub fn balanced_openings<G: EngineGame>(start: &G, plies: u32) -> Vec<Vec<G::Move>> {
+    let mut lines = Vec::new();
+    let mut seen = HashSet::new();
+    collect_lines(&mut start.clone(), plies, &mut Vec::new(), &mut seen, &mut lines);
+
+    let mut ai = G::new_ai(Difficulty::Hard);
+    let mut scored: Vec<(i32, Vec<G::Move>)> = Vec::new();
+    for line in lines {
+        let mut game = start.clone();
+        for &mv in &line {
+            game.play(mv);
+        }
+        let mut score = 0;
+        ai.analyze(&mut game, BALANCE_DEPTH, Budget::unlimited(), &mut |info| score = info.score);
+        if score != i32::MAX && score != i32::MIN {
+            scored.push((score, line));
+        }
+    }
+    // sorting keeps lines with the same score in the order they were found
+    scored.sort_by_key(|(score, _)| score.unsigned_abs());
+    let keep = scored.len().div_ceil(2);
+    scored.into_iter().take(keep).map(|(_, line)| line).collect()
+}
+
+fn collect_lines<G: Game>(game: &mut G, plies: u32, line: &mut Vec<G::Move>, seen: &mut HashSet<u64>, lines: &mut Vec<Vec<G::Move>>) {
+    // every line the same length leaves the same side to move, so the pieces
+    // alone tell positions apart
+    if plies == 0 {
+        if seen.insert(game.zobrist_key()) {
+            lines.push(line.clone());
+        }
+        return;
+    }
+    if game.outcome() != Outcome::InProgress {
+        return;
+    }
+    for mv in game.legal_moves() {
+        game.play(mv);
+        line.push(mv);
+        collect_lines(game, plies - 1, line, seen, lines);
+        line.pop();
+        game.undo(mv);
+    }
+}
+
+/// Plays `games` games between `first` and `second` from `start`, going
+/// through `openings` in order with each played twice, the first setup
+/// taking the first seat in even games and the second seat in odd ones.
+/// `on_game` hears about each game as it ends, with its number from 0, the
+/// first setup's seat, the opening and the result.
+pub fn play_match<G: TournamentGame>(
+    start: &G,
+    openings: &[Vec<G::Move>],
+    games: u32,
+    first: &EngineSetup<G::Weights>,
+    second: &EngineSetup<G::Weights>,
+    think_millis: Option<u32>,
+    mut on_game: impl FnMut(u32, Player, &[G::Move], GameResult),
+) -> MatchResult {
+    let mut result = MatchResult::default();
+    for number in 0..games {
+        let opening: &[G::Move] = match openings {
+            [] => &[],
+            _ => &openings[(number as usize / 2) % openings.len()],
+        };
+        let seat = if number % 2 == 0 { Player::One } else { Player::Two };
+        let outcome = play_game(start, opening, seat, G::contestant(first), G::contestant(second), think_millis);
+        match outcome {
+            GameResult::Win => result.wins += 1,
+            GameResult::Draw => result.draws += 1,
+            GameResult::Loss => result.losses += 1,
+        }
+        on_game(number, seat, opening, outcome);
+    }
+    result
+}
+
+// plays one game from `opening`, `first` playing `seat` and `second` the other
+fn play_game<G: TournamentGame>(start: &G, opening: &[G::Move], seat: Player, mut first: G::Ai, mut second: G::Ai, think_millis: Option<u32>) -> GameResult {
+    let mut game = start.clone();
+    for &mv in opening {
+        game.commit(mv);
+    }
+    while game.outcome() == Outcome::InProgress && !game.legal_moves().is_empty() {
+        let ai = if game.side_to_move() == seat { &mut first } else { &mut second };
+        let mv = match think_millis {
+            Some(millis) => ai.choose_move_with_budget(&mut game, millis),
+            None => ai.choose_move(&mut game),
+        };
+        game.commit(mv);
+    }
+    match game.outcome() {
+        Outcome::Win(winner) if winner == seat => GameResult::Win,
+        Outcome::Win(_) => GameResult::Loss,
+        Outcome::Draw | Outcome::InProgress => GameResult::Draw,
+    }
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The first setup's points per game, a draw counting half
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// The 95% confidence interval around score
+    pub fn score_interval(&self) -> (f64, f64) {
+        let (n, score) = (self.games() as f64, self.score());
+        let spread = self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2);
+        let error = (spread / n).sqrt() / n.sqrt();
+        ((score - Z_95 * error).max(0.0), (score + Z_95 * error).min(1.0))
+    }
+
+    /// How many Elo points stronger the first setup looks, infinite if it
+    /// won or lost every game
+    pub fn elo(&self) -> f64 {
+        elo_difference(self.score())
+    }
+
+    /// The 95% confidence interval around elo
+    pub fn elo_interval(&self) -> (f64, f64) {
+        let (low, high) = self.score_interval();
+        (elo_difference(low), elo_difference(high))
+    }
+}
+
+// the rating gap at which the stronger player expects `score` points a game
+fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// the 95% interval around `count` out of `games`, as percentages
+fn percent_interval(count: u32, games: u32) -> (f64, f64) {
+    let share = count as f64 / games as f64;
+    let error = Z_95 * (share * (1.0 - share) / games as f64).sqrt();
+    ((share - error).max(0.0) * 100.0, (share + error).min(1.0) * 100.0)
+}
+
+struct Elo(f64);
+
+impl fmt::Display for Elo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            elo if elo.is_infinite() && elo > 0.0 => write!(f, "+inf"),
+            elo if elo.is_infinite() => write!(f, "-inf"),
+            // adding zero turns -0 into 0
+            elo => write!(f, "{:+}", elo.round() + 0.0),
+        }
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let games = self.games();
+        if games == 0 {
+            return write!(f, "No games played.");
+        }
+        writeln!(f, "Games: {}", games)?;
+        for (name, count) in [("Wins", self.wins), ("Draws", self.draws), ("Losses", self.losses)] {
+            let (low, high) = percent_interval(count, games);
+            writeln!(f, "{}: {} ({:.1}%, 95% CI {:.1}% to {:.1}%)", name, count, count as f64 * 100.0 / games as f64, low, high)?;
+        }
+        let (low, high) = self.score_interval();
+        writeln!(f, "Score: {:.1}% (95% CI {:.1}% to {:.1}%)", self.score() * 100.0, low * 100.0, high * 100.0)?;
+        let (low, high) = self.elo_interval();
+        write!(f, "Elo difference: {} (95% CI {} to {})", Elo(self.elo()), Elo(low), Elo(high))
+    }
+}
+
+impl TournamentGame for Connect4Board {
+    type Weights = Connect4Weights;
+
+    fn set_weight(weights: &mut Connect4Weights, name: &str, value: i32) -> bool {
+        let weight = match name {
+            "center" => &mut weights.center,
+            "line" => &mut weights.line,
+            "three" => &mut weights.three,
+            "two" => &mut weights.two,
+            "block" => &mut weights.block,
+            "kept" => &mut weights.kept,
+            _ => return false,
+        };
+        *weight = value;
+        true
+    }
+
+    fn contestant(setup: &EngineSetup<Connect4Weights>) -> Connect4AI {
+        let mut ai = Connect4AI::new(setup.difficulty);
+        if let Some(depth) = setup.depth {
+            ai.set_depth(depth);
+        }
+        if let Some(iterations) = setup.iterations {
+            ai.set_iterations(iterations);
+        }
+        if let Some(rollout) = setup.rollout {
+            ai.set_rollout(rollout);
+        }
+        ai.set_weights(setup.weights);
+        ai
+    }
+}
+
+impl TournamentGame for TootOttoBoard {
+    type Weights = OttoWeights;
+
+    fn set_weight(weights: &mut OttoWeights, name: &str, value: i32) -> bool {
+        let weight = match name {
+            "center" => &mut weights.center,
+            "word" => &mut weights.word,
+            "three" => &mut weights.three,
+            "two" => &mut weights.two,
+            "block" => &mut weights.block,
+            _ => return false,
+        };
+        *weight = value;
+        true
+    }
+
+    fn contestant(setup: &EngineSetup<OttoWeights>) -> OttoBot {
+        let mut ai = OttoBot::new(setup.difficulty, Player::One);
+        if let Some(depth) = setup.depth {
+            ai.set_depth(depth);
+        }
+        if let Some(iterations) = setup.iterations {
+            ai.set_iterations(iterations);
+        }
+        if let Some(rollout) = setup.rollout {
+            ai.set_rollout(rollout);
+        }
+        ai.set_weights(setup.weights);
+        ai
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardSize;
+
+    // shallow enough that a match is over in moments
+    fn quick() -> EngineSetup<Connect4Weights> {
+        EngineSetup { difficulty: Difficulty::Hard, depth: Some(2), iterations: None, rollout: None, weights: Connect4Weights::default() }
+    }
+
+    #[test]
+    fn an_even_match_is_no_elo_either_way() {
+        let result = MatchResult { wins: 3, draws: 4, losses: 3 };
+        assert_eq!(result.games(), 10);
+        assert_eq!(result.score(), 0.5);
+        assert_eq!(result.elo(), 0.0);
+        assert_eq!(Elo(result.elo()).to_string(), "+0");
+        let (low, high) = result.elo_interval();
+        assert!(low < 0.0 && high > 0.0);
+    }
+
+    #[test]
+    fn three_points_in_four_is_191_elo() {
+        let result = MatchResult { wins: 3, draws: 0, losses: 1 };
+        assert_eq!(result.score(), 0.75);
+        assert_eq!(Elo(result.elo()).to_string(), "+191");
+        assert_eq!(Elo(MatchResult { wins: 1, draws: 0, losses: 3 }.elo()).to_string(), "-191");
+    }
+
+    #[test]
+    fn a_clean_sweep_is_infinitely_stronger() {
+        let result = MatchResult { wins: 4, draws: 0, losses: 0 };
+        assert_eq!(result.score_interval(), (1.0, 1.0));
+        assert!(result.to_string().ends_with("Elo difference: +inf (95% CI +inf to +inf)"));
+        assert_eq!(MatchResult::default().to_string(), "No games played.");
+    }
+
+    #[test]
+    fn openings_end_in_different_positions() {
+        let start = Connect4Board::new(BoardSize::Standard);
+        let openings = balanced_openings(&start, 2);
+        assert!(!openings.is_empty() && openings.len() <= 25);
+        let mut keys = HashSet::new();
+        for line in &openings {
+            assert_eq!(line.len(), 2);
+            let mut game = start.clone();
+            line.iter().for_each(|&mv| game.play(mv));
+            assert!(keys.insert(game.zobrist_key()));
+        }
+    }
+
+    #[test]
+    fn a_match_plays_every_game_swapping_seats() {
+        let start = Connect4Board::new(BoardSize::Standard);
+        let openings = balanced_openings(&start, 2);
+        let setup = quick();
+        let mut seats = Vec::new();
+        let result = play_match(&start, &openings, 4, &setup, &setup, None, |number, seat, opening, _| {
+            assert_eq!(opening, &openings[number as usize / 2][..]);
+            seats.push(seat);
+        });
+        assert_eq!(result.games(), 4);
+        assert_eq!(seats, [Player::One, Player::Two, Player::One, Player::Two]);
+    }
+
+    #[test]
+    fn unknown_weights_are_refused() {
+        let mut weights = Connect4Weights::default();
+        assert!(<Connect4Board as TournamentGame>::set_weight(&mut weights, "center", 9));
+        assert_eq!(weights.center, 9);
+        assert!(!<Connect4Board as TournamentGame>::set_weight(&mut weights, "word", 9));
+    }
+}
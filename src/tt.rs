@@ -0,0 +1,98 @@
+//! Bounded transposition table shared by the minimax engines
+//!
+//! Entries are indexed by Zobrist key. Each slot holds one position; a new
+//! entry replaces the old one unless the old one is the same position searched
+//! deeper. The table keeps its contents between calls so the next search
+//! starts from everything the previous one learned.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    // the stored score is exact
+    Exact,
+    // the search failed high, the real score is at least this
+    Lower,
+    // the search failed low, the real score is at most this
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<M> {
+    pub key: u64,
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<M>,
+    /// no line under the position stopped at the depth limit, so searching
+    /// deeper can't change the score
+    pub complete: bool,
+}
+
+pub struct TranspositionTable<M> {
+    entries: Vec<Option<Entry<M>>>,
+}
+
+impl<M: Copy> TranspositionTable<M> {
+    /// Creates a table with `capacity` slots, rounded up to a power of two
+    pub fn new(capacity: usize) -> TranspositionTable<M> {
+        TranspositionTable { entries: vec![None; capacity.next_power_of_two()] }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry<M>> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    pub fn store(&mut self, entry: Entry<M>) {
+        let index = self.index(entry.key);
+        match self.entries[index] {
+            Some(old) if old.key == entry.key && old.depth > entry.depth => (),
+            _ => self.entries[index] = Some(entry),
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: u32, score: i32) -> Entry<u32> {
+        Entry { key, depth, score, bound: Bound::Exact, best_move: Some(3), complete: false }
+    }
+
+    #[test]
+    fn finds_what_was_stored() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(5, 2, 40));
+        let found = table.probe(5).unwrap();
+        assert_eq!((found.depth, found.score, found.best_move), (2, 40, Some(3)));
+        assert!(table.probe(6).is_none());
+    }
+
+    #[test]
+    fn keeps_the_deeper_search_of_a_position() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(5, 6, 40));
+        table.store(entry(5, 2, -10));
+        assert_eq!(table.probe(5).unwrap().score, 40);
+        table.store(entry(5, 8, -10));
+        assert_eq!(table.probe(5).unwrap().score, -10);
+    }
+
+    #[test]
+    fn another_position_takes_the_slot() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(5, 6, 40));
+        table.store(entry(5 + 16, 1, 7));
+        assert!(table.probe(5).is_none());
+        assert_eq!(table.probe(5 + 16).unwrap().score, 7);
+        table.clear();
+        assert!(table.probe(5 + 16).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! Zobrist keys shared by both boards
+//!
+//! Every (cell, piece) pair gets a random 64 bit key and a position hashes to
+//! the XOR of the keys of its occupied cells, so boards can update their hash
+//! with a single XOR when a piece is placed or removed. The keys come from a
+//! fixed seed so hashes are the same from one run to the next.
+
+use lazy_static::lazy_static;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::board::MAX_WIDTH;
+
+// enough for every cell index either board hands out
+pub const MAX_CELLS: usize = 128;
+
+// indexed by Player in Connect 4, and by Player and Token together in TOOT
+// and OTTO since who placed a token decides whose tiles are left
+pub const PIECES: usize = 4;
+
+// more discs than anyone keeps in Pop 10 before the game is over
+pub const MAX_KEPT: usize = 16;
+
+lazy_static! {
+    static ref KEYS: Vec<[u64; PIECES]> = {
+        let mut rng = StdRng::seed_from_u64(0x0c0f_fee4);
+        (0..MAX_CELLS).map(|_| std::array::from_fn(|_| rng.gen())).collect()
+    };
+    static ref SIDE_KEYS: [u64; PIECES] = {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        std::array::from_fn(|seat| if seat == 0 { 0 } else { rng.gen() })
+    };
+    static ref KEPT_KEYS: Vec<[u64; MAX_KEPT]> = {
+        let mut rng = StdRng::seed_from_u64(0x6e97);
+        (0..PIECES).map(|_| std::array::from_fn(|_| rng.gen())).collect()
+    };
+    static ref PENDING_KEYS: [u64; MAX_WIDTH as usize] = {
+        let mut rng = StdRng::seed_from_u64(0x9e4d);
+        std::array::from_fn(|_| rng.gen())
+    };
+}
+
+/// Key for `piece` sitting on `cell`
+pub fn piece_key(cell: usize, piece: usize) -> u64 {
+    KEYS[cell][piece]
+}
+
+/// Mixed into the hash by the engines when the player in `seat`, counting
+/// from 0, is to move. The first player's is 0.
+pub fn side_key(seat: usize) -> u64 {
+    SIDE_KEYS[seat]
+}
+
+/// Key for `player` having kept `count` discs in Pop 10, 0 for none so the
+/// other variants' hashes don't change
+pub fn kept_key(player: usize, count: u32) -> u64 {
+    match count {
+        0 => 0,
+        count => KEPT_KEYS[player][count as usize],
+    }
+}
+
+/// Key for a popped disc in Pop 10 waiting to go back into `col`
+pub fn pending_key(col: u32) -> u64 {
+    PENDING_KEYS[col as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BoardSize, Token};
+    use crate::connect4::{Connect4Board, Connect4Move};
+    use crate::game::Game;
+    use crate::otto::{OttoMove, TootOttoBoard};
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys: Vec<u64> = (0..MAX_CELLS).flat_map(|cell| (0..PIECES).map(move |piece| piece_key(cell, piece))).collect();
+        keys.extend((1..PIECES).map(side_key));
+        keys.extend((0..PIECES).flat_map(|player| (1..MAX_KEPT as u32).map(move |count| kept_key(player, count))));
+        keys.extend((0..MAX_WIDTH).map(pending_key));
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+
+    #[test]
+    fn connect4_transpositions_hash_the_same() {
+        let mut first = Connect4Board::new(BoardSize::Standard);
+        let mut second = Connect4Board::new(BoardSize::Standard);
+        let empty = first.zobrist_key();
+        for col in [3, 2, 4, 5] {
+            first.play(Connect4Move::drop(col));
+        }
+        for col in [4, 5, 3, 2] {
+            second.play(Connect4Move::drop(col));
+        }
+        assert_eq!(first.zobrist_key(), second.zobrist_key());
+        assert_ne!(first.zobrist_key(), empty);
+        for col in [5, 4, 2, 3] {
+            Game::undo(&mut first, Connect4Move::drop(col));
+        }
+        assert_eq!(first.zobrist_key(), empty);
+    }
+
+    #[test]
+    fn otto_keys_tell_tokens_apart() {
+        let mut t = TootOttoBoard::new(BoardSize::Standard);
+        let mut o = TootOttoBoard::new(BoardSize::Standard);
+        let empty = t.zobrist_key();
+        t.play(OttoMove::drop(2, Token::T));
+        o.play(OttoMove::drop(2, Token::O));
+        assert_ne!(t.zobrist_key(), o.zobrist_key());
+        Game::undo(&mut t, OttoMove::drop(2, Token::T));
+        assert_eq!(t.zobrist_key(), empty);
+    }
+}
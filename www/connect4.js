@@ -1,10 +1,9 @@
-import { BoardSize, Difficulty, Connect4Board, Connect4AI } from "connect4";
+import { BoardSize, Difficulty, Connect4Board, Connect4AI, Player, GameStatus } from "connect4";
 
 class GameData {
     constructor(size, mode) {
         this.size = size == 0 ? BoardSize.Standard : BoardSize.Large;
         this.mode = this.get_mode(mode);
-        this.turn = 'X';
         this.winner = null; // 0: player 1, 1: player 2, 2: draw
         this.backendBoard = new Connect4Board(this.size);
         this.ai = this.mode !== null ? new Connect4AI(this.mode) : null;
@@ -20,17 +19,14 @@ class GameData {
         }
     }
 
-    nextTurn() {
-        this.turn = this.turn === 'X' ? 'O' : 'X';
-    }
-
     getWinner() {
-        if (this.backendBoard.is_draw()) {
-            this.winner = 2;
-        } else if (this.backendBoard.last_player() == 'X') {
-            this.winner = 0;
-        } else {
-            this.winner = 1;
+        switch (this.backendBoard.status()) {
+            case GameStatus.Draw:
+                this.winner = 2;
+                break;
+            case GameStatus.Win:
+                this.winner = this.backendBoard.winner() === Player.One ? 0 : 1;
+                break;
         }
         return this.winner;
     }
@@ -42,8 +38,8 @@ export function drawBoard(size, mode, gameName) {
     var rows, cols, gameBoard;
     var game = new GameData(size, mode);
     gameBoard = document.getElementById('connect4GameBoard');
-    rows = game.size == BoardSize.Standard ? 6 : 7;
-    cols = game.size == BoardSize.Standard ? 7 : 10;
+    rows = game.backendBoard.height();
+    cols = game.backendBoard.width();
 
     gameBoard.innerHTML = ''; 
     var table = document.createElement('table');
@@ -70,6 +66,20 @@ export function drawBoard(size, mode, gameName) {
         table.appendChild(row);
     }
     gameBoard.appendChild(table);
+
+    var undoButton = document.createElement('button');
+    undoButton.textContent = "Undo";
+    undoButton.onclick = function() {
+        undoMove(game);
+    };
+    gameBoard.appendChild(undoButton);
+
+    var redoButton = document.createElement('button');
+    redoButton.textContent = "Redo";
+    redoButton.onclick = function() {
+        redoMove(game);
+    };
+    gameBoard.appendChild(redoButton);
 }
 
 
@@ -83,7 +93,9 @@ function getPlayerMove(cell_selected, game) {
         return;
     }
 
-    performMove(cellId, game);
+    if (!performMove(cellId, game)) {
+        return;
+    }
 
     // AI move
     if (!endGame(game) && game.ai != null) {
@@ -109,7 +121,9 @@ function endGame(game) {
 function getAIMove(game) {
     var maxRows = game.backendBoard.height();
     var maxCols = game.backendBoard.width();
-    var selectedColumn = game.ai.best_move(game.backendBoard, 'O');
+    var aiMove = game.ai.best_move(game.backendBoard);
+    var selectedColumn = aiMove.col;
+    aiMove.free();
     var cellId = getEmptyCell(selectedColumn, maxRows, maxCols);
 
     if (cellId == -1) {
@@ -121,6 +135,35 @@ function getAIMove(game) {
     var bool = endGame(game);
 }
 
+// takes back moves until it's a person's turn, skipping the computer's replies
+function undoMove(game) {
+    var record;
+    while ((record = game.backendBoard.undo()) !== undefined) {
+        var cell = document.getElementById('b' + (record.row * game.backendBoard.width() + record.col));
+        cell.classList.remove('yellow-filled', 'red-filled');
+        cell.classList.add('empty-cell');
+        record.free();
+        if (game.ai == null || game.backendBoard.side_to_move() !== Player.Two) {
+            break;
+        }
+    }
+    game.winner = null;
+}
+
+// plays moves taken back again until it's a person's turn
+function redoMove(game) {
+    var record;
+    while ((record = game.backendBoard.redo()) !== undefined) {
+        var cell = document.getElementById('b' + (record.row * game.backendBoard.width() + record.col));
+        cell.classList.remove('empty-cell');
+        cell.classList.add(record.player === Player.One ? 'yellow-filled' : 'red-filled');
+        record.free();
+        if (endGame(game) || game.ai == null || game.backendBoard.side_to_move() !== Player.Two) {
+            break;
+        }
+    }
+}
+
 function getEmptyCell(selectedColumn, maxRows, maxCols) {
     for (var i = maxRows - 1; i >= 0; i--) {
         var cellId = 'b' + (i * maxCols + selectedColumn);
@@ -133,18 +176,21 @@ function getEmptyCell(selectedColumn, maxRows, maxCols) {
 }
 
 function performMove(cellId, game) {
+    let col = parseInt(cellId.substring(1), 10) % game.backendBoard.width();
+    let player = game.backendBoard.side_to_move();
+    try {
+        game.backendBoard.try_play(col, player).free();
+    } catch (e) {
+        alert(e.message);
+        return false;
+    }
     let cell = document.getElementById(cellId);
     console.log("cellId: ", cellId);
     cell.classList.remove('empty-cell');
-    var piece;
-    if (game.turn == 'X') {
+    if (player === Player.One) {
         cell.classList.add('yellow-filled') 
-        piece = 'X';
     } else {
         cell.classList.add('red-filled');
-        piece = 'O';
     }
-    let col = parseInt(cellId.substring(1), 10) % game.backendBoard.width();
-    game.backendBoard.perform_move(col, piece);
-    game.nextTurn();
+    return true;
 }
\ No newline at end of file
@@ -1,14 +1,15 @@
-import { BoardSize,Difficulty,TootOttoBoard,OttoBot } from "connect4";
+import { BoardSize,Difficulty,TootOttoBoard,OttoBot,Player,Token,GameStatus } from "connect4";
 
 class GameBoard {
     constructor(size, mode, playerTok) {
         this.size = size == 0 ? BoardSize.Standard : BoardSize.Large;
         this.board = new TootOttoBoard(this.size);
         this.mode = mode; // 0 for player vs player, 1 for easy AI, 2 for hard AI
-        this.winner = null; // 0 for O win, 1 for T win, 2 for draw, 3 for tie
-        this.turn = 'T'; // T for Toot, O for Otto
-        this.playerTok = playerTok;
-        this.ai = this.mode !== null ? new OttoBot(this.get_mode(this.mode), this.playerTok == 'T' ? 'O' : 'T') : null;
+        this.winner = null; // 0 for O win, 1 for T win, 2 for draw or tie
+        this.player = playerTok == 'O' ? Player.One : Player.Two; // Player.One is Otto, Player.Two is Toot
+        this.board.set_side_to_move(this.player); // whoever picked a token goes first
+        this.aiPlayer = this.player === Player.One ? Player.Two : Player.One;
+        this.ai = this.mode !== null ? new OttoBot(this.get_mode(this.mode), this.aiPlayer) : null;
     }
 
     get_mode(mode) {
@@ -21,10 +22,6 @@ class GameBoard {
         }
     }
 
-    nextTurn() {
-        this.turn = this.turn === 'T' ? 'O' : 'T';
-    }
-
     updateBoard(row, col, piece) {
         this.board[row][col] = piece;
     }
@@ -35,14 +32,12 @@ export function drawBoardToot(size, mode, playerTok) {
     var rows, cols, gameBoard
     var game = new GameBoard(size, mode, playerTok);
     gameBoard = document.getElementById('TootOttoGameBoard');
-    rows = game.size == BoardSize.Standard ? 4 : 6;
-    cols = game.size == BoardSize.Standard ? 6 : 9;
+    rows = game.board.height();
+    cols = game.board.width();
     gameBoard.innerHTML = ''; 
     var table = document.createElement('table');
     table.className = "ui";
 
-    game.turn = playerTok;
-
     console.log("drawBoardToot called");
 
     for (var i = 0; i < rows; i++) {
@@ -56,7 +51,7 @@ export function drawBoardToot(size, mode, playerTok) {
             input.className = ["cell", "empty-cell"].join(' ');
             input.readOnly = true;
             input.onclick = function() {
-                if (game.winner === null && game.board.has_winner() ==='f') {
+                if (game.winner === null && game.board.status() === GameStatus.InProgress) {
                     startTurn(this.id, game);
                 }
             };
@@ -66,6 +61,20 @@ export function drawBoardToot(size, mode, playerTok) {
         table.appendChild(row);
     }
     gameBoard.appendChild(table);
+
+    var undoButton = document.createElement('button');
+    undoButton.textContent = "Undo";
+    undoButton.onclick = function() {
+        undoMove(game);
+    };
+    gameBoard.appendChild(undoButton);
+
+    var redoButton = document.createElement('button');
+    redoButton.textContent = "Redo";
+    redoButton.onclick = function() {
+        redoMove(game);
+    };
+    gameBoard.appendChild(redoButton);
 }
 
 
@@ -79,7 +88,9 @@ function startTurn(cell_selected, game) {
         return;
     }
 
-    performMove(cellId, game);
+    if (!performMove(cellId, game)) {
+        return;
+    }
     
     // AI move
     if (!endGame(game) && game.mode != 0) {
@@ -89,42 +100,34 @@ function startTurn(cell_selected, game) {
 
 function endGame(game) {
 
-    let result = game.board.has_winner();
-    if (result === 'w') {
-        //there is a winner, we should get winner
-        let winner = game.board.get_winner();
-        if (winner === 'T') {
-            game.winner = 1;
-            alert("TOOT HAS WON!");
-        } else {
-            game.winner = 0;
-            alert("OTTO HAS WON!");
-        }
-    }else if (result === 'd') {
-        game.winner = 2;
-        alert("DRAW!");
-    }else if (result === 't') {
-        game.winner = 3;
-        alert("TIE!");
-    }
-
-    if (!game.board.is_terminal()) {
-        return false;
+    switch (game.board.status()) {
+        case GameStatus.Win:
+            //there is a winner, we should get winner
+            if (game.board.winner() === Player.Two) {
+                game.winner = 1;
+                alert("TOOT HAS WON!");
+            } else {
+                game.winner = 0;
+                alert("OTTO HAS WON!");
+            }
+            return true;
+        case GameStatus.Draw:
+            game.winner = 2;
+            alert("DRAW!");
+            return true;
+        default:
+            return false;
     }
-
-    return true;
 }
 
 function getAIMove(game) {
     console.log("AI move");
     var maxRows = game.board.height();
     var maxCols = game.board.width();
-    var ai_token = game.playerTok == 'T' ? 'O' : 'T';
-    console.log("AI token: " + ai_token);
-    var selectedColumnandtoken= game.ai.best_move(game.board, ai_token);
-    console.log("AI move: " + selectedColumnandtoken);
-    var selectedColumn = parseInt(selectedColumnandtoken.substring(0,1), 10);
-    var token = selectedColumnandtoken.substring(1,2);
+    var aiMove = game.ai.best_move(game.board);
+    var selectedColumn = aiMove.col;
+    var token = aiMove.token === Token.T ? 'T' : 'O';
+    aiMove.free();
     var cellId = getEmptyCell(selectedColumn, maxRows, maxCols);
 
     console.log("AI move: " + cellId);
@@ -134,11 +137,45 @@ function getAIMove(game) {
         return;
     }
     
-    performMoveAI(cellId,token, game);
+    if (!performMoveAI(cellId, token, game)) {
+        return;
+    }
     
     var bool = endGame(game);
 }
 
+// takes back moves until it's a person's turn, skipping the computer's replies
+function undoMove(game) {
+    var record;
+    while ((record = game.board.undo()) !== undefined) {
+        var cell = document.getElementById('b' + (record.row * game.board.width() + record.col));
+        cell.classList.remove('toot-token', 'otto-token');
+        cell.classList.add('empty-cell');
+        cell.value = '';
+        record.free();
+        if (game.mode == 0 || game.board.side_to_move() !== game.aiPlayer) {
+            break;
+        }
+    }
+    game.winner = null;
+}
+
+// plays moves taken back again until it's a person's turn
+function redoMove(game) {
+    var record;
+    while ((record = game.board.redo()) !== undefined) {
+        var cell = document.getElementById('b' + (record.row * game.board.width() + record.col));
+        var piece = record.token === Token.T ? 'T' : 'O';
+        cell.classList.remove('empty-cell');
+        cell.classList.add(piece == 'T' ? 'toot-token' : 'otto-token');
+        cell.value = piece;
+        record.free();
+        if (endGame(game) || game.mode == 0 || game.board.side_to_move() !== game.aiPlayer) {
+            break;
+        }
+    }
+}
+
 function getEmptyCell(selectedColumn, maxRows, maxCols) {
     for (var i = maxRows - 1; i >= 0; i--) {
         var cellId = 'b' + (i * maxCols + selectedColumn);
@@ -151,8 +188,6 @@ function getEmptyCell(selectedColumn, maxRows, maxCols) {
 }
 
 function performMove(cellId, game) {
-    let cell = document.getElementById(cellId);
-    cell.classList.remove('empty-cell');
     var piece;
     var tokens = document.getElementsByName('token');
     for (var i = 0; i < tokens.length; i++) {
@@ -162,7 +197,16 @@ function performMove(cellId, game) {
         }
     }
         
-    let col = parseInt(cellId.substring(1), 10) % game.width;
+    let col = parseInt(cellId.substring(1), 10) % game.board.width();
+    try {
+        game.board.try_play(col, piece == 'T' ? Token.T : Token.O, game.board.side_to_move()).free();
+    } catch (e) {
+        alert(e.message);
+        return false;
+    }
+
+    let cell = document.getElementById(cellId);
+    cell.classList.remove('empty-cell');
 
     if (piece == 'T') {
         cell.classList.add('toot-token');
@@ -172,22 +216,26 @@ function performMove(cellId, game) {
         cell.value = piece;
     }
 
-    var turn = game.turn == 'T' ? 'T' : 'O';
-    console.log("Turn " + turn)
-    game.board.perform_move_plz(col, piece, turn);
     console.log("Player move: " + cellId + " " + piece);
     var bool = endGame(game);
-    game.nextTurn();
+    return true;
 }
 
 
 function performMoveAI(cellId, ai_token, game) {
     //funciton to hanlde AI move
-    let cell = document.getElementById(cellId);
-    cell.classList.remove('empty-cell');
     var piece = ai_token;
         
-    let col = parseInt(cellId.substring(1), 10) % game.width;
+    let col = parseInt(cellId.substring(1), 10) % game.board.width();
+    try {
+        game.board.try_play(col, piece == 'T' ? Token.T : Token.O, game.aiPlayer).free();
+    } catch (e) {
+        alert(e.message);
+        return false;
+    }
+
+    let cell = document.getElementById(cellId);
+    cell.classList.remove('empty-cell');
 
     if (piece == 'T') {
         cell.classList.add('toot-token');
@@ -196,9 +244,5 @@ function performMoveAI(cellId, ai_token, game) {
         cell.classList.add('otto-token');
         cell.value = piece;
     }
-
-    var turn = game.turn == 'O' ? 'O' : 'T';
-    console.log("AI Turn " + turn)
-    game.board.perform_move_plz(col, piece, turn);
-    game.nextTurn();
+    return true;
 }